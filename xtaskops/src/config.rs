@@ -0,0 +1,180 @@
+//!
+//! Project-level task defaults, read from `[workspace.metadata.xtask]` in `Cargo.toml`
+//!
+//! ```toml
+//! [workspace.metadata.xtask]
+//! coverage-format = "lcov"
+//! coverage-tolerance = 0.5
+//! min-coverage = 80.0
+//! clippy-max = false
+//! jobs = 4
+//! clippy-lints = ["-W", "clippy::pedantic", "-A", "clippy::module_name_repetitions"]
+//! ```
+//!
+use crate::ops::{cargo_metadata, Metadata};
+use crate::tasks::CoverageFormat;
+use anyhow::{anyhow, Context, Result as AnyResult};
+
+/// Task defaults from `[workspace.metadata.xtask]`. Keys that are not set are `None`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct XtaskConfig {
+    /// default coverage output format (`coverage-format`)
+    pub coverage_format: Option<CoverageFormat>,
+
+    /// accepted coverage drop for `coverage_diff`, in percentage points (`coverage-tolerance`)
+    pub coverage_tolerance: Option<f32>,
+
+    /// minimum total line coverage, in percent, for the coverage task (`min-coverage`)
+    pub min_coverage: Option<f32>,
+
+    /// run CI with nightly (`nightly`)
+    pub nightly: Option<bool>,
+
+    /// toolchain to run CI with, e.g. `beta` or `1.75.0` (`toolchain`)
+    pub toolchain: Option<String>,
+
+    /// turn all clippy lints on in CI (`clippy-max`)
+    pub clippy_max: Option<bool>,
+
+    /// number of parallel jobs for CI and coverage (`jobs`)
+    pub jobs: Option<usize>,
+
+    /// clippy lint flags for CI, replacing the `clippy-max` set (`clippy-lints`)
+    pub clippy_lints: Option<Vec<String>>,
+}
+
+impl XtaskConfig {
+    /// Loads the config from the current workspace's `cargo metadata`
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if cargo metadata fails or a key has the wrong type or value
+    pub fn load() -> AnyResult<Self> {
+        Self::from_metadata(&cargo_metadata()?)
+    }
+
+    /// Reads the config from a parsed `cargo metadata` document
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if a key has the wrong type or value
+    pub fn from_metadata(metadata: &Metadata) -> AnyResult<Self> {
+        let Some(table) = metadata.metadata.as_ref().and_then(|m| m.get("xtask")) else {
+            return Ok(Self::default());
+        };
+        let key = |name: &str| table.get(name).filter(|v| !v.is_null());
+        let invalid = |name: &str, expected: &str| {
+            anyhow!("[workspace.metadata.xtask] `{name}` must be {expected}")
+        };
+
+        let string = |name: &str| {
+            key(name)
+                .map(|v| {
+                    v.as_str()
+                        .map(String::from)
+                        .ok_or_else(|| invalid(name, "a string"))
+                })
+                .transpose()
+        };
+        let boolean = |name: &str| {
+            key(name)
+                .map(|v| v.as_bool().ok_or_else(|| invalid(name, "a boolean")))
+                .transpose()
+        };
+
+        #[allow(clippy::cast_possible_truncation)]
+        let number = |name: &str| {
+            key(name)
+                .map(|v| {
+                    v.as_f64()
+                        .map(|f| f as f32)
+                        .ok_or_else(|| invalid(name, "a number"))
+                })
+                .transpose()
+        };
+        let jobs = key("jobs")
+            .map(|v| {
+                v.as_u64()
+                    .and_then(|n| usize::try_from(n).ok())
+                    .ok_or_else(|| invalid("jobs", "a positive integer"))
+            })
+            .transpose()?;
+
+        let clippy_lints = key("clippy-lints")
+            .map(|v| {
+                v.as_array()
+                    .and_then(|a| {
+                        a.iter()
+                            .map(|l| l.as_str().map(String::from))
+                            .collect::<Option<Vec<_>>>()
+                    })
+                    .ok_or_else(|| invalid("clippy-lints", "an array of strings"))
+            })
+            .transpose()?;
+
+        Ok(Self {
+            coverage_format: string("coverage-format")?
+                .map(|f| {
+                    f.parse()
+                        .context("[workspace.metadata.xtask] `coverage-format` is invalid")
+                })
+                .transpose()?,
+            coverage_tolerance: number("coverage-tolerance")?,
+            min_coverage: number("min-coverage")?,
+            nightly: boolean("nightly")?,
+            toolchain: string("toolchain")?,
+            clippy_max: boolean("clippy-max")?,
+            jobs,
+            clippy_lints,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata(xtask: &str) -> Metadata {
+        Metadata::from_json(&format!(
+            r#"{{
+                "workspace_root": "/ws",
+                "target_directory": "/ws/target",
+                "workspace_members": [],
+                "packages": [],
+                "metadata": {{"xtask": {xtask}}}
+            }}"#
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn reads_the_xtask_table() {
+        let config = XtaskConfig::from_metadata(&metadata(
+            r#"{"coverage-format": "lcov", "jobs": 2, "clippy-lints": ["-W", "clippy::pedantic"]}"#,
+        ))
+        .unwrap();
+        assert_eq!(config.coverage_format, Some(CoverageFormat::Lcov));
+        assert_eq!(config.jobs, Some(2));
+        assert_eq!(
+            config.clippy_lints,
+            Some(vec!["-W".to_string(), "clippy::pedantic".to_string()])
+        );
+    }
+
+    #[test]
+    fn missing_table_is_the_default() {
+        let metadata = Metadata::from_json(
+            r#"{"workspace_root": "/ws", "target_directory": "/t", "workspace_members": [], "packages": []}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            XtaskConfig::from_metadata(&metadata).unwrap(),
+            XtaskConfig::default()
+        );
+    }
+
+    #[test]
+    fn rejects_wrong_types() {
+        assert!(XtaskConfig::from_metadata(&metadata(r#"{"jobs": "two"}"#)).is_err());
+    }
+}
@@ -0,0 +1,123 @@
+//!
+//! Errors returned by the `ops` building blocks, so callers can match on the
+//! kind of failure. They convert into `anyhow::Error` with `?` like any other error.
+//!
+use std::{error::Error, fmt, io, path::PathBuf, process::ExitStatus, time::Duration};
+
+/// Result with an [`XtaskError`]
+pub type Result<T, E = XtaskError> = std::result::Result<T, E>;
+
+/// What went wrong in an `ops` function
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum XtaskError {
+    /// a command ran but exited unsuccessfully
+    CommandFailed {
+        /// the command, e.g. `cargo metadata`
+        name: String,
+        /// its exit status
+        status: ExitStatus,
+    },
+    /// a file system or process operation failed
+    Io(io::Error),
+    /// `cargo metadata` output was missing a field or could not be parsed
+    Metadata(String),
+    /// a path that must exist does not
+    NotFound(PathBuf),
+    /// a glob pattern is invalid
+    Pattern(glob::PatternError),
+    /// a prompt needs an answer but there is no terminal to ask on
+    NotInteractive(String),
+    /// an option or argument has a value that cannot be used
+    InvalidArgument(String),
+    /// a command ran longer than allowed and was killed
+    Timeout {
+        /// the command
+        name: String,
+        /// the time it was allowed
+        timeout: Duration,
+    },
+}
+
+impl fmt::Display for XtaskError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::CommandFailed { name, status } => write!(f, "`{name}` failed ({status})"),
+            Self::Io(e) => e.fmt(f),
+            Self::Metadata(msg) => write!(f, "cannot read cargo metadata: {msg}"),
+            Self::NotFound(path) => write!(f, "{} not found", path.display()),
+            Self::Pattern(e) => write!(f, "invalid glob pattern: {e}"),
+            Self::InvalidArgument(msg) => write!(f, "invalid argument: {msg}"),
+            Self::Timeout { name, timeout } => {
+                write!(f, "`{name}` timed out after {:.1}s", timeout.as_secs_f32())
+            }
+            Self::NotInteractive(question) => {
+                write!(
+                    f,
+                    "cannot ask \"{question}\" without an interactive terminal"
+                )
+            }
+        }
+    }
+}
+
+impl Error for XtaskError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            Self::Pattern(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for XtaskError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<glob::PatternError> for XtaskError {
+    fn from(e: glob::PatternError) -> Self {
+        Self::Pattern(e)
+    }
+}
+
+impl From<glob::GlobError> for XtaskError {
+    fn from(e: glob::GlobError) -> Self {
+        Self::Io(e.into_error())
+    }
+}
+
+impl From<ignore::Error> for XtaskError {
+    fn from(e: ignore::Error) -> Self {
+        let kind = e.io_error().map_or(io::ErrorKind::Other, io::Error::kind);
+        Self::Io(io::Error::new(kind, e))
+    }
+}
+
+impl From<fs_extra::error::Error> for XtaskError {
+    fn from(e: fs_extra::error::Error) -> Self {
+        use fs_extra::error::ErrorKind as Fsx;
+        let message = e.to_string();
+        let kind = match e.kind {
+            Fsx::Io(io) => return Self::Io(io),
+            Fsx::NotFound => io::ErrorKind::NotFound,
+            Fsx::PermissionDenied => io::ErrorKind::PermissionDenied,
+            Fsx::AlreadyExists => io::ErrorKind::AlreadyExists,
+            Fsx::Interrupted => io::ErrorKind::Interrupted,
+            Fsx::InvalidFolder => io::ErrorKind::NotADirectory,
+            Fsx::InvalidFile | Fsx::InvalidFileName | Fsx::InvalidPath => {
+                io::ErrorKind::InvalidInput
+            }
+            _ => io::ErrorKind::Other,
+        };
+        Self::Io(io::Error::new(kind, message))
+    }
+}
+
+impl From<serde_json::Error> for XtaskError {
+    fn from(e: serde_json::Error) -> Self {
+        Self::Metadata(e.to_string())
+    }
+}
@@ -1,9 +1,10 @@
 //!
 //! `xtaskops` contains common tasks and operations for handling a Rust repo with xtask
 //!
-#![feature(io_error_more)]
 #![allow(clippy::must_use_candidate)]
 #![warn(missing_docs)] // uncomment for docs
 
+pub mod config;
+pub mod error;
 pub mod ops;
 pub mod tasks;
@@ -3,30 +3,162 @@
 //!
 //!
 
-use anyhow::{anyhow, Result as AnyResult};
-use dialoguer::{theme::ColorfulTheme, Confirm};
+use crate::error::{Result, XtaskError};
+use anyhow::Result as AnyResult;
+use derive_builder::Builder;
+use dialoguer::{theme::ColorfulTheme, Confirm, Input, MultiSelect, Select};
+use duct::Expression;
 use fs_extra as fsx;
 use fsx::dir::CopyOptions;
-use glob::glob;
-use serde_json::Value;
+use glob::{glob, Pattern};
+use ignore::WalkBuilder;
+use serde::Deserialize;
 use std::{
     env,
-    fs::{create_dir_all, read_dir, remove_dir_all},
-    io::{self, ErrorKind},
-    path::{Path, PathBuf},
+    ffi::{OsStr, OsString},
+    fmt,
+    fs::{self, create_dir_all, read_dir, remove_dir_all, File},
+    io::{self, ErrorKind, IsTerminal, Read, Write},
+    path::{Component, Path, PathBuf},
+    process::{self, ExitStatus},
     str::FromStr,
+    sync::{
+        atomic::{AtomicBool, AtomicU8, Ordering},
+        Mutex, PoisonError,
+    },
+    thread,
+    time::{Duration, Instant},
 };
+use toml_edit::DocumentMut;
 
 pub use duct::cmd;
+
+/// A file system change that dry-run mode skipped
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PlannedOp {
+    /// remove a file
+    RemoveFile(PathBuf),
+    /// remove a directory with its contents
+    RemoveDir(PathBuf),
+    /// create a directory and its parents
+    CreateDir(PathBuf),
+    /// write, or overwrite, a file
+    WriteFile(PathBuf),
+    /// run a command that changes the project, e.g. `git commit`
+    RunCommand(String),
+}
+
+impl fmt::Display for PlannedOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::RemoveFile(path) => write!(f, "remove file {}", path.display()),
+            Self::RemoveDir(path) => write!(f, "remove directory {}", path.display()),
+            Self::CreateDir(path) => write!(f, "create directory {}", path.display()),
+            Self::WriteFile(path) => write!(f, "write file {}", path.display()),
+            Self::RunCommand(command) => write!(f, "run `{command}`"),
+        }
+    }
+}
+
+static DRY_RUN: AtomicBool = AtomicBool::new(false);
+static PLANNED: Mutex<Vec<PlannedOp>> = Mutex::new(Vec::new());
+
+///
+/// Turn dry-run mode on or off for this process. While on, the destructive
+/// operations (`clean_files` and friends, `remove_file`, `remove_dir`,
+/// `get_clean_directory`, `write_atomic`) and the release and minimal-versions
+/// tasks only report what they would change; see `planned_operations`.
+///
+pub fn set_dry_run(dry_run: bool) {
+    DRY_RUN.store(dry_run, Ordering::Relaxed);
+}
+
+///
+/// Whether dry-run mode is on, see `set_dry_run`
+///
+pub fn is_dry_run() -> bool {
+    DRY_RUN.load(Ordering::Relaxed)
+}
+
+///
+/// Take the operations skipped in dry-run mode so far, oldest first
+///
+pub fn planned_operations() -> Vec<PlannedOp> {
+    std::mem::take(&mut *PLANNED.lock().unwrap_or_else(PoisonError::into_inner))
+}
+
+/// In dry-run mode, records and reports `op` and returns true: the caller skips it
+pub(crate) fn planned(op: PlannedOp) -> bool {
+    if !is_dry_run() {
+        return false;
+    }
+    plan(op);
+    true
+}
+
+/// Records and reports `op` as skipped, for tasks with a dry run of their own
+pub(crate) fn plan(op: PlannedOp) {
+    status(&format!("dry run: would {op}"), "33");
+    PLANNED
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .push(op);
+}
 ///
 /// Remove a set of files given a glob
 ///
 /// # Errors
-/// Fails if listing or removal fails
+/// Fails if the pattern is invalid, or listing or removal fails
+///
+pub fn clean_files(pattern: &str) -> Result<()> {
+    clean_files_with_paths(pattern).map(|_| ())
+}
+
+///
+/// Remove a set of files given a glob, returning the removed files, sorted
+///
+/// # Errors
+/// Fails if the pattern is invalid, or listing or removal fails
+///
+pub fn clean_files_with_paths(pattern: &str) -> Result<Vec<PathBuf>> {
+    let mut removed = vec![];
+    for path in clean_files_dry_run(pattern)? {
+        if remove_file_if_exists(&path)? {
+            removed.push(path);
+        }
+    }
+    Ok(removed)
+}
+
+///
+/// Remove the files matching the `include` glob, except those matching any
+/// of the `excludes` globs, e.g. `clean_files_except("**/*.tmp", &["target/keep/**"])`
+///
+/// # Errors
+/// Fails if a pattern is invalid, or listing or removal fails
 ///
-pub fn clean_files(pattern: &str) -> AnyResult<()> {
-    let files: Result<Vec<PathBuf>, _> = glob(pattern)?.collect();
-    files?.iter().try_for_each(remove_file)
+pub fn clean_files_except(include: &str, excludes: &[&str]) -> Result<()> {
+    let excludes = excludes
+        .iter()
+        .map(|pattern| Pattern::new(pattern))
+        .collect::<Result<Vec<_>, _>>()?;
+    clean_files_dry_run(include)?
+        .iter()
+        .filter(|path| !excludes.iter().any(|e| e.matches_path(path)))
+        .try_for_each(remove_file)
+}
+
+///
+/// List the files `clean_files` would remove for a glob, without removing
+/// anything. Paths are sorted.
+///
+/// # Errors
+/// Fails if the pattern is invalid or listing fails
+///
+pub fn clean_files_dry_run(pattern: &str) -> Result<Vec<PathBuf>> {
+    let mut files = glob(pattern)?.collect::<Result<Vec<PathBuf>, _>>()?;
+    files.sort();
+    Ok(files)
 }
 /// . removes all contents of directory or create it recursively if it does not exist
 ///
@@ -35,8 +167,14 @@ pub fn clean_files(pattern: &str) -> AnyResult<()> {
 /// This function will return an error if .
 pub fn get_clean_directory(path: &PathBuf) -> io::Result<()> {
     if !path.exists() {
+        if planned(PlannedOp::CreateDir(path.clone())) {
+            return Ok(());
+        }
         create_dir_all(path)
     } else if path.is_dir() {
+        if planned(PlannedOp::RemoveDir(path.clone())) {
+            return Ok(());
+        }
         remove_dir_all(path)
     } else {
         Err(io::Error::new(
@@ -52,11 +190,38 @@ pub fn get_clean_directory(path: &PathBuf) -> io::Result<()> {
 /// # Errors
 /// Fails if removal fails
 ///
-pub fn remove_file<P>(path: P) -> AnyResult<()>
+pub fn remove_file<P>(path: P) -> Result<()>
+where
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    if path.exists() && planned(PlannedOp::RemoveFile(path.to_path_buf())) {
+        return Ok(());
+    }
+    Ok(fsx::file::remove(path)?)
+}
+
+///
+/// Remove a single file if it is there. A missing file is not an error, and
+/// unlike checking for it first, one removed concurrently is not either.
+/// Returns whether the file was removed.
+///
+/// # Errors
+/// Fails if removal fails for any other reason, such as permissions
+///
+pub fn remove_file_if_exists<P>(path: P) -> Result<bool>
 where
     P: AsRef<Path>,
 {
-    fsx::file::remove(path).map_err(anyhow::Error::msg)
+    let path = path.as_ref();
+    if is_dry_run() {
+        return Ok(path.exists() && planned(PlannedOp::RemoveFile(path.to_path_buf())));
+    }
+    match fs::remove_file(path) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e.into()),
+    }
 }
 
 ///
@@ -65,11 +230,15 @@ where
 /// # Errors
 /// Fails if removal fails
 ///
-pub fn remove_dir<P>(path: P) -> AnyResult<()>
+pub fn remove_dir<P>(path: P) -> Result<()>
 where
     P: AsRef<Path>,
 {
-    fsx::dir::remove(path).map_err(anyhow::Error::msg)
+    let path = path.as_ref();
+    if path.exists() && planned(PlannedOp::RemoveDir(path.to_path_buf())) {
+        return Ok(());
+    }
+    Ok(fsx::dir::remove(path)?)
 }
 
 ///
@@ -82,13 +251,112 @@ where
     std::path::Path::exists(path.as_ref())
 }
 
+///
+/// Express `target` relative to `base`, e.g. `/ws/coverage/html` relative to
+/// `/ws/xtask` is `../coverage/html`. Returns `None` when there is no such
+/// path, for example when mixing absolute and relative paths.
+///
+pub fn relative_path(base: &Path, target: &Path) -> Option<PathBuf> {
+    if base.is_absolute() != target.is_absolute() {
+        return None;
+    }
+    let mut base_comps = base.components();
+    let mut target_comps = target.components();
+    let mut comps: Vec<Component<'_>> = vec![];
+    loop {
+        match (target_comps.next(), base_comps.next()) {
+            (None, None) => break,
+            (Some(t), None) => {
+                comps.push(t);
+                comps.extend(target_comps.by_ref());
+                break;
+            }
+            (None, _) => comps.push(Component::ParentDir),
+            (Some(t), Some(b)) if comps.is_empty() && t == b => (),
+            (Some(t), Some(Component::CurDir)) => comps.push(t),
+            (Some(_), Some(Component::ParentDir)) => return None,
+            (Some(t), Some(_)) => {
+                comps.push(Component::ParentDir);
+                comps.extend(base_comps.by_ref().map(|_| Component::ParentDir));
+                comps.push(t);
+                comps.extend(target_comps.by_ref());
+                break;
+            }
+        }
+    }
+    Some(comps.into_iter().collect())
+}
+
+///
+/// List the Rust files under any `src` directory below `root`, honoring
+/// `.gitignore` and friends. Paths are sorted.
+///
+/// # Errors
+/// Fails if walking the tree fails
+///
+pub fn source_files<P>(root: P) -> Result<Vec<PathBuf>>
+where
+    P: AsRef<Path>,
+{
+    let root = root.as_ref();
+    let mut files = vec![];
+    for entry in WalkBuilder::new(root).build() {
+        let path = entry?.into_path();
+        let in_src = relative_path(root, &path)
+            .is_some_and(|rel| rel.components().any(|c| c.as_os_str() == "src"));
+        if in_src && path.is_file() && path.extension().is_some_and(|ext| ext == "rs") {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+///
+/// Write a file atomically: contents go to a temporary file in the same
+/// directory, which is then renamed into place
+///
+/// # Errors
+/// Fails if writing or renaming fails
+///
+pub fn write_atomic<P>(path: P, contents: &[u8]) -> Result<()>
+where
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    let file_name = path.file_name().ok_or_else(|| {
+        io::Error::new(
+            ErrorKind::InvalidInput,
+            format!("not a file path: {}", path.display()),
+        )
+    })?;
+    if planned(PlannedOp::WriteFile(path.to_path_buf())) {
+        return Ok(());
+    }
+    let tmp = path.with_file_name(format!(
+        ".{}.{}.tmp",
+        file_name.to_string_lossy(),
+        process::id()
+    ));
+
+    let res = File::create(&tmp).and_then(|mut f| {
+        f.write_all(contents)?;
+        f.sync_all()?;
+        fs::rename(&tmp, path)
+    });
+    if res.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    Ok(res?)
+}
+
 ///
 /// Copy entire folder contents
 ///
 /// # Errors
 /// Fails if file operations fail
 ///
-pub fn copy_contents<P, Q>(from: P, to: Q, overwrite: bool) -> AnyResult<u64>
+pub fn copy_contents<P, Q>(from: P, to: Q, overwrite: bool) -> Result<u64>
 where
     P: AsRef<Path>,
     Q: AsRef<Path>,
@@ -96,11 +364,204 @@ where
     let mut opts = CopyOptions::new();
     opts.content_only = true;
     opts.overwrite = overwrite;
-    fsx::dir::copy(from, to, &opts).map_err(anyhow::Error::msg)
+    Ok(fsx::dir::copy(from, to, &opts)?)
+}
+///
+/// Copy entire folder contents, calling `progress` with the bytes copied so
+/// far and the total bytes as the copy goes, e.g. to drive a progress bar
+///
+/// # Errors
+/// Fails if file operations fail
+///
+pub fn copy_contents_with_progress<P, Q, F>(
+    from: P,
+    to: Q,
+    overwrite: bool,
+    mut progress: F,
+) -> Result<u64>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+    F: FnMut(u64, u64),
+{
+    let mut opts = CopyOptions::new();
+    opts.content_only = true;
+    opts.overwrite = overwrite;
+    Ok(fsx::dir::copy_with_progress(from, to, &opts, |p| {
+        progress(p.copied_bytes, p.total_bytes);
+        fsx::dir::TransitProcessResult::ContinueOrAbort
+    })?)
+}
+
+///
+/// Copy the folder contents accepted by `filter`, which is called with each
+/// path relative to `from`. Rejecting a directory skips everything below it,
+/// and directories are only created to hold copied files. Returns the number
+/// of bytes copied.
+///
+/// ```no_run
+/// use xtaskops::ops::copy_contents_filtered;
+/// copy_contents_filtered("template", "out", false, |p| {
+///     !p.starts_with(".git") && !p.starts_with("target") && p.extension() != Some("bak".as_ref())
+/// })?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+///
+/// # Errors
+/// Fails if `from` is not a directory, a file exists in `to` and `overwrite`
+/// is not set, or file operations fail
+///
+pub fn copy_contents_filtered<P, Q, F>(from: P, to: Q, overwrite: bool, filter: F) -> Result<u64>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+    F: Fn(&Path) -> bool,
+{
+    let from = from.as_ref();
+    if !from.is_dir() {
+        return Err(XtaskError::NotFound(from.to_path_buf()));
+    }
+    let opts = TreeCopy {
+        overwrite,
+        symlinks: false,
+        filter: Some(&filter),
+    };
+    copy_tree(from, to.as_ref(), Path::new(""), &opts)
+}
+
+/// Build a folder contents copy with more options than `copy_contents`
+#[derive(Builder)]
+#[builder(setter(into))]
+pub struct CopyContents {
+    /// folder whose contents are copied
+    pub from: PathBuf,
+
+    /// folder to copy into
+    pub to: PathBuf,
+
+    /// replace files that exist in `to`
+    /// default: off
+    #[builder(default = "false")]
+    pub overwrite: bool,
+
+    /// recreate symlinks as symlinks with the same (possibly relative) target,
+    /// instead of copying what they point to. Supported on unix and Windows
+    /// (where creating links may need extra privileges); elsewhere links are
+    /// followed as if this was off.
+    /// default: off
+    #[builder(default = "false")]
+    pub copy_symlinks: bool,
+}
+
+impl CopyContentsBuilder {
+    /// Builds and runs the copy, returning the number of bytes copied
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `from` is not a directory, a file
+    /// exists in `to` and `overwrite` is not set, or file operations fail
+    pub fn run(&self) -> Result<u64> {
+        let t = self
+            .build()
+            .map_err(|e| XtaskError::InvalidArgument(e.to_string()))?;
+        if !t.copy_symlinks || cfg!(not(any(unix, windows))) {
+            return copy_contents(&t.from, &t.to, t.overwrite);
+        }
+        if !t.from.is_dir() {
+            return Err(XtaskError::NotFound(t.from));
+        }
+        let opts = TreeCopy {
+            overwrite: t.overwrite,
+            symlinks: true,
+            filter: None,
+        };
+        copy_tree(&t.from, &t.to, Path::new(""), &opts)
+    }
+}
+
+struct TreeCopy<'a> {
+    overwrite: bool,
+    symlinks: bool,
+    /// with a filter, directories are only created to hold copied files
+    filter: Option<&'a dyn Fn(&Path) -> bool>,
+}
+
+fn copy_tree(from: &Path, to: &Path, rel: &Path, opts: &TreeCopy<'_>) -> Result<u64> {
+    let mut copied = 0;
+    for entry in read_dir(from.join(rel))? {
+        let entry = entry?;
+        let rel = rel.join(entry.file_name());
+        if opts.filter.is_some_and(|filter| !filter(&rel)) {
+            continue;
+        }
+        let source = entry.path();
+        let target = to.join(&rel);
+        let is_link = opts.symlinks && entry.file_type()?.is_symlink();
+        if source.is_dir() && !is_link {
+            if opts.filter.is_none() {
+                create_dir_all(&target)?;
+            }
+            copied += copy_tree(from, to, &rel, opts)?;
+            continue;
+        }
+        if let Ok(existing) = target.symlink_metadata() {
+            if !opts.overwrite {
+                return Err(io::Error::new(
+                    ErrorKind::AlreadyExists,
+                    format!("{} already exists", target.display()),
+                )
+                .into());
+            }
+            // replace links rather than writing through them
+            if is_link || existing.file_type().is_symlink() {
+                fs::remove_file(&target)?;
+            }
+        }
+        if let Some(parent) = target.parent() {
+            create_dir_all(parent)?;
+        }
+        if is_link {
+            symlink(&fs::read_link(&source)?, &target, source.is_dir())?;
+        } else {
+            copied += fs::copy(&source, &target)?;
+        }
+    }
+    Ok(copied)
+}
+
+#[cfg(unix)]
+fn symlink(original: &Path, link: &Path, _is_dir: bool) -> io::Result<()> {
+    std::os::unix::fs::symlink(original, link)
+}
+
+#[cfg(windows)]
+fn symlink(original: &Path, link: &Path, is_dir: bool) -> io::Result<()> {
+    if is_dir {
+        std::os::windows::fs::symlink_dir(original, link)
+    } else {
+        std::os::windows::fs::symlink_file(original, link)
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+fn symlink(_original: &Path, _link: &Path, _is_dir: bool) -> io::Result<()> {
+    Err(io::Error::new(
+        ErrorKind::Unsupported,
+        "symlinks are not supported on this platform",
+    ))
 }
+
+///
+/// Move entire folder contents, leaving `from` removed. Like `copy_contents`,
+/// `to` is created if needed and merged into if it exists. Returns the number
+/// of bytes moved.
 ///
-/// Move entire folder contents
-pub fn move_contents<P, Q>(from: P, to: Q, overwrite: bool) -> AnyResult<u64>
+/// # Errors
+/// Fails if `from` does not exist or is not a directory, a file exists in `to`
+/// and `overwrite` is not set, or file operations fail. Files moved before the
+/// failure stay moved.
+///
+pub fn move_contents<P, Q>(from: P, to: Q, overwrite: bool) -> Result<u64>
 where
     P: AsRef<Path>,
     Q: AsRef<Path>,
@@ -108,7 +569,7 @@ where
     let mut opts = CopyOptions::new();
     opts.content_only = true;
     opts.overwrite = overwrite;
-    fsx::dir::move_dir(from, to, &opts).map_err(anyhow::Error::msg)
+    Ok(fsx::dir::move_dir(from, to, &opts)?)
 }
 
 ///
@@ -118,27 +579,650 @@ where
 /// Panics if input interaction fails
 ///
 pub fn confirm(question: &str) -> bool {
+    confirm_with_default(question, false)
+}
+
+///
+/// Prompt the user to confirm an action, answering `default` when they just
+/// press enter, e.g. `Delete target? [Y/n]` with `true`
+///
+/// # Panics
+/// Panics if input interaction fails
+///
+pub fn confirm_with_default(question: &str, default: bool) -> bool {
     Confirm::with_theme(&ColorfulTheme::default())
         .with_prompt(question)
+        .default(default)
         .interact()
         .unwrap()
 }
 
 ///
-/// Gets the cargo root dir
+/// Prompt the user for a line of text, e.g. a version or tag
+///
+/// # Errors
+/// Fails if not `is_interactive`, or input interaction fails
+///
+pub fn prompt_input(question: &str) -> Result<String> {
+    if !is_interactive() {
+        return Err(XtaskError::NotInteractive(question.to_string()));
+    }
+    Ok(Input::with_theme(&ColorfulTheme::default())
+        .with_prompt(question)
+        .interact_text()?)
+}
+
+///
+/// Prompt the user for a line of text, answering `default` when they just press enter
+///
+/// # Errors
+/// Fails if not `is_interactive`, or input interaction fails
+///
+pub fn prompt_input_with_default(question: &str, default: &str) -> Result<String> {
+    if !is_interactive() {
+        return Err(XtaskError::NotInteractive(question.to_string()));
+    }
+    Ok(Input::with_theme(&ColorfulTheme::default())
+        .with_prompt(question)
+        .default(default.to_string())
+        .interact_text()?)
+}
+
+///
+/// Prompt the user to pick one of `items`, returning its index
+///
+/// # Errors
+/// Fails if not `is_interactive`, or input interaction fails
+///
+pub fn prompt_select(question: &str, items: &[&str]) -> Result<usize> {
+    if !is_interactive() {
+        return Err(XtaskError::NotInteractive(question.to_string()));
+    }
+    Ok(Select::with_theme(&ColorfulTheme::default())
+        .with_prompt(question)
+        .items(items)
+        .default(0)
+        .interact()?)
+}
+
+///
+/// Prompt the user to pick any number of `items`, returning their indices
+///
+/// # Errors
+/// Fails if not `is_interactive`, or input interaction fails
+///
+pub fn prompt_multi_select(question: &str, items: &[&str]) -> Result<Vec<usize>> {
+    if !is_interactive() {
+        return Err(XtaskError::NotInteractive(question.to_string()));
+    }
+    Ok(MultiSelect::with_theme(&ColorfulTheme::default())
+        .with_prompt(question)
+        .items(items)
+        .interact()?)
+}
+
+static ASSUME_YES: AtomicBool = AtomicBool::new(false);
+
+///
+/// Answer yes to every `confirm_or` prompt from now on, e.g. for a `--yes` flag
+///
+pub fn set_assume_yes(yes: bool) {
+    ASSUME_YES.store(yes, Ordering::Relaxed);
+}
+
+///
+/// Check whether the user can answer prompts: stdin is a terminal and the
+/// `CI` environment variable is not set
+///
+pub fn is_interactive() -> bool {
+    let ci = env::var_os("CI").is_some_and(|v| !v.is_empty() && v != "false");
+    stdin_is_tty() && !ci
+}
+
+///
+/// Prompt the user to confirm an action without ever blocking or panicking
+/// in automation: answers yes after `set_assume_yes`, `default` when not
+/// `is_interactive` or when the interaction fails, and otherwise asks with
+/// `default` preselected
+///
+pub fn confirm_or(question: &str, default: bool) -> bool {
+    if ASSUME_YES.load(Ordering::Relaxed) {
+        return true;
+    }
+    if !is_interactive() {
+        return default;
+    }
+    Confirm::with_theme(&ColorfulTheme::default())
+        .with_prompt(question)
+        .default(default)
+        .interact()
+        .unwrap_or(default)
+}
+
+///
+/// Append `extra` to the flags in the environment variable `var`
+/// (e.g. `RUSTFLAGS`), keeping whatever the user already set
+///
+pub fn merged_flags(var: &str, extra: &str) -> String {
+    match env::var(var) {
+        Ok(existing) if !existing.trim().is_empty() => format!("{} {extra}", existing.trim()),
+        _ => extra.to_string(),
+    }
+}
+
+///
+/// Sets an environment variable for the whole process until the guard is
+/// dropped, then restores the previous value (or removes the variable).
+/// Commands spawned meanwhile inherit it.
+///
+/// ```no_run
+/// use xtaskops::ops::{cmd, EnvGuard};
+/// let _color = EnvGuard::set("CARGO_TERM_COLOR", "always");
+/// cmd!("cargo", "build").run()?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+///
+#[must_use = "the variable is restored as soon as the guard is dropped"]
+pub struct EnvGuard {
+    key: OsString,
+    previous: Option<OsString>,
+}
+
+impl EnvGuard {
+    /// Sets `key` to `value` until the returned guard is dropped
+    pub fn set<K, V>(key: K, value: V) -> Self
+    where
+        K: AsRef<OsStr>,
+        V: AsRef<OsStr>,
+    {
+        let key = key.as_ref().to_os_string();
+        let previous = env::var_os(&key);
+        env::set_var(&key, value);
+        Self { key, previous }
+    }
+}
+
+impl Drop for EnvGuard {
+    fn drop(&mut self) {
+        match self.previous.take() {
+            Some(previous) => env::set_var(&self.key, previous),
+            None => env::remove_var(&self.key),
+        }
+    }
+}
+
+///
+/// Run a command, streaming its combined stdout and stderr to the terminal
+/// while also capturing it. Returns the exit code and the captured output;
+/// a non-zero exit code is not an error.
+///
+/// # Errors
+/// Fails if the command cannot be started or its output cannot be read
+///
+pub fn run_tee<I, S>(program: &str, args: I) -> Result<(i32, String)>
+where
+    I: IntoIterator<Item = S>,
+    S: Into<OsString>,
+{
+    let reader = cmd(program, args).stderr_to_stdout().unchecked().reader()?;
+    let mut stdout = io::stdout();
+    let mut captured = vec![];
+    let mut buf = [0u8; 8192];
+    loop {
+        let n = (&reader).read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        stdout.write_all(&buf[..n])?;
+        stdout.flush()?;
+        captured.extend_from_slice(&buf[..n]);
+    }
+    let status = reader
+        .try_wait()?
+        .map(|out| out.status)
+        .ok_or_else(|| io::Error::other(format!("{program} did not exit")))?;
+    Ok((
+        status.code().unwrap_or(-1),
+        String::from_utf8_lossy(&captured).into_owned(),
+    ))
+}
+
+/// Captured result of `run_captured`
+#[derive(Debug, Clone)]
+pub struct CommandOutput {
+    /// standard output, lossily decoded as UTF-8
+    pub stdout: String,
+    /// standard error, lossily decoded as UTF-8
+    pub stderr: String,
+    /// exit status
+    pub status: ExitStatus,
+}
+
+impl CommandOutput {
+    /// Whether the command exited successfully
+    pub fn success(&self) -> bool {
+        self.status.success()
+    }
+}
+
+///
+/// Run a command, capturing its stdout and stderr separately. A non-zero exit
+/// code is not an error; check `status`.
+///
+/// # Errors
+/// Fails if the command cannot be started
+///
+pub fn run_captured<I, S>(program: &str, args: I) -> Result<CommandOutput>
+where
+    I: IntoIterator<Item = S>,
+    S: Into<OsString>,
+{
+    let output = cmd(program, args)
+        .stdout_capture()
+        .stderr_capture()
+        .unchecked()
+        .run()?;
+    Ok(CommandOutput {
+        stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+        stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        status: output.status,
+    })
+}
+
+///
+/// Run a command, running it again up to `retries` times when it exits
+/// unsuccessfully, waiting `backoff` before each retry. Meant for steps that
+/// fail transiently, such as downloads on a flaky network.
+///
+/// # Errors
+/// Fails with the last attempt's failure when all attempts failed, or right
+/// away when the command cannot be started
+///
+pub fn run_with_retries<S>(
+    program: &str,
+    args: &[S],
+    retries: usize,
+    backoff: Duration,
+) -> Result<()>
+where
+    S: AsRef<OsStr>,
+{
+    let mut attempt = 0;
+    loop {
+        let exit = cmd(program, args).unchecked().run()?.status;
+        if exit.success() {
+            return Ok(());
+        }
+        if attempt == retries {
+            return Err(XtaskError::CommandFailed {
+                name: program.to_string(),
+                status: exit,
+            });
+        }
+        attempt += 1;
+        status(
+            &format!(
+                "{program} failed, retrying in {:.1}s ({attempt}/{retries})",
+                backoff.as_secs_f32()
+            ),
+            "33",
+        );
+        thread::sleep(backoff);
+    }
+}
+
+///
+/// Run a command, killing it when it runs longer than `timeout`, e.g. as a
+/// guard against hung tests or watchers. Only the process started here is
+/// killed; processes it spawned itself may outlive it.
+///
+/// # Errors
+/// Fails if the command cannot be started, exits unsuccessfully, or times out
+///
+pub fn run_with_timeout<I, S>(program: &str, args: I, timeout: Duration) -> Result<()>
+where
+    I: IntoIterator<Item = S>,
+    S: Into<OsString>,
+{
+    let handle = cmd(program, args).unchecked().start()?;
+    let started = Instant::now();
+    loop {
+        if let Some(output) = handle.try_wait()? {
+            if output.status.success() {
+                return Ok(());
+            }
+            return Err(XtaskError::CommandFailed {
+                name: program.to_string(),
+                status: output.status,
+            });
+        }
+        if started.elapsed() >= timeout {
+            // kills and then waits for the child, so it is not left running
+            handle.kill()?;
+            return Err(XtaskError::Timeout {
+                name: program.to_string(),
+                timeout,
+            });
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+}
+
+///
+/// Check whether a nightly toolchain is installed
+///
+pub fn nightly_available() -> bool {
+    succeeds(&cmd!("cargo", "+nightly", "--version"))
+}
+
+///
+/// Run a command quietly and report whether it exited successfully.
+/// Handy for probing whether a tool is installed.
+///
+pub fn succeeds(expression: &Expression) -> bool {
+    expression
+        .stdout_null()
+        .stderr_null()
+        .unchecked()
+        .run()
+        .is_ok_and(|out| out.status.success())
+}
+
+/// When to use colored output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorChoice {
+    /// color when writing to a terminal and `NO_COLOR` is not set
+    #[default]
+    Auto,
+    /// always color
+    Always,
+    /// never color
+    Never,
+}
+
+impl FromStr for ColorChoice {
+    type Err = XtaskError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(Self::Auto),
+            "always" => Ok(Self::Always),
+            "never" => Ok(Self::Never),
+            _ => Err(XtaskError::InvalidArgument(format!(
+                "unknown color choice `{s}`, expected auto, always or never"
+            ))),
+        }
+    }
+}
+
+static COLOR_CHOICE: AtomicU8 = AtomicU8::new(ColorChoice::Auto as u8);
+
+///
+/// Set the color choice for this process: it applies to the crate's own
+/// messages and is passed to cargo through `CARGO_TERM_COLOR`. With `Auto`,
+/// `CARGO_TERM_COLOR` is cleared so cargo goes back to its own heuristics,
+/// unless `NO_COLOR` is set.
+///
+pub fn set_color(choice: ColorChoice) {
+    COLOR_CHOICE.store(choice as u8, Ordering::Relaxed);
+    let cargo_color = match choice {
+        ColorChoice::Always => Some("always"),
+        ColorChoice::Never => Some("never"),
+        ColorChoice::Auto if no_color_env() => Some("never"),
+        ColorChoice::Auto => None,
+    };
+    match cargo_color {
+        Some(cargo_color) => env::set_var("CARGO_TERM_COLOR", cargo_color),
+        None => env::remove_var("CARGO_TERM_COLOR"),
+    }
+}
+
+fn no_color_env() -> bool {
+    env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty())
+}
+
+///
+/// Whether the crate's own messages should be colored, per `set_color`. They
+/// go to stderr, so `auto` colors when stderr is a terminal.
+///
+pub fn colors_enabled() -> bool {
+    match COLOR_CHOICE.load(Ordering::Relaxed) {
+        c if c == ColorChoice::Always as u8 => true,
+        c if c == ColorChoice::Never as u8 => false,
+        _ => !no_color_env() && stderr_is_tty(),
+    }
+}
+
+/// How much output tasks ask cargo for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Verbosity {
+    /// `--quiet`: only warnings, errors and the commands' own results
+    Quiet,
+    /// cargo's default output
+    #[default]
+    Normal,
+    /// `--verbose`
+    Verbose,
+}
+
+impl Verbosity {
+    /// Flags to pass to a cargo subcommand for this verbosity
+    pub fn cargo_args(self) -> &'static [&'static str] {
+        match self {
+            Self::Quiet => &["--quiet"],
+            Self::Normal => &[],
+            Self::Verbose => &["--verbose"],
+        }
+    }
+}
+
+static VERBOSITY: AtomicU8 = AtomicU8::new(Verbosity::Normal as u8);
+
+///
+/// Set the verbosity of the cargo commands tasks run, for this process
+///
+pub fn set_verbosity(verbosity: Verbosity) {
+    VERBOSITY.store(verbosity as u8, Ordering::Relaxed);
+}
+
+///
+/// The verbosity set with `set_verbosity`
+///
+pub fn verbosity() -> Verbosity {
+    match VERBOSITY.load(Ordering::Relaxed) {
+        v if v == Verbosity::Quiet as u8 => Verbosity::Quiet,
+        v if v == Verbosity::Verbose as u8 => Verbosity::Verbose,
+        _ => Verbosity::Normal,
+    }
+}
+
+pub(crate) fn paint(text: &str, ansi: &str) -> String {
+    if colors_enabled() {
+        format!("\x1b[{ansi}m{text}\x1b[0m")
+    } else {
+        text.to_string()
+    }
+}
+
+///
+/// Report task progress such as `=== generating report ===`. With the `log`
+/// feature and a logger installed this is an `info!` record, otherwise the
+/// message is printed to stderr, colored with `ansi` when colors are enabled.
+///
+pub(crate) fn status(message: &str, ansi: &str) {
+    #[cfg(feature = "log")]
+    if log::max_level() != log::LevelFilter::Off {
+        log::info!("{message}");
+        return;
+    }
+    eprintln!("{}", paint(message, ansi));
+}
+
+const SPINNER_FRAMES: [&str; 10] = ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+
+///
+/// Open a file with the system's default application, e.g. a report in the browser.
+/// Uses `open` on macOS, `start` on Windows and `xdg-open` elsewhere.
+///
+/// # Errors
+/// Errors if the file does not exist or the opener failed
+///
+pub fn open_path<P: AsRef<Path>>(path: P) -> Result<()> {
+    let path = path.as_ref();
+    if !path.exists() {
+        return Err(XtaskError::NotFound(path.to_path_buf()));
+    }
+    let (name, opener) = if cfg!(target_os = "macos") {
+        ("open", cmd!("open", path))
+    } else if cfg!(windows) {
+        ("start", cmd!("cmd", "/C", "start", "", path))
+    } else {
+        ("xdg-open", cmd!("xdg-open", path))
+    };
+    let status = opener.unchecked().run()?.status;
+    if !status.success() {
+        return Err(XtaskError::CommandFailed {
+            name: name.to_string(),
+            status,
+        });
+    }
+    Ok(())
+}
+
+///
+/// Check whether stdout is a terminal, as opposed to a pipe or a CI log
+///
+pub fn is_tty() -> bool {
+    io::stdout().is_terminal()
+}
+
+///
+/// Check whether stdin is a terminal, i.e. the user can answer prompts
+///
+pub fn stdin_is_tty() -> bool {
+    io::stdin().is_terminal()
+}
+
+///
+/// Check whether stderr is a terminal
+///
+pub fn stderr_is_tty() -> bool {
+    io::stderr().is_terminal()
+}
+
+///
+/// Run `f` while showing a spinner with `message`. When stderr is not a
+/// terminal (e.g. in CI) plain `=== message ===` / `ok.` lines are printed instead.
+///
+/// # Errors
+/// Fails if `f` fails
+///
+pub fn with_spinner<T, E, F>(message: &str, f: F) -> Result<T, E>
+where
+    F: FnOnce() -> Result<T, E>,
+{
+    if !stderr_is_tty() {
+        status(&format!("=== {message} ==="), "1");
+        let res = f()?;
+        status("ok.", "32");
+        return Ok(res);
+    }
+
+    let done = AtomicBool::new(false);
+    let res = thread::scope(|s| {
+        s.spawn(|| {
+            let mut stderr = io::stderr();
+            for frame in SPINNER_FRAMES.iter().cycle() {
+                if done.load(Ordering::Relaxed) {
+                    break;
+                }
+                let _ = write!(stderr, "\r{frame} {message}");
+                let _ = stderr.flush();
+                thread::sleep(Duration::from_millis(80));
+            }
+        });
+        let res = f();
+        done.store(true, Ordering::Relaxed);
+        res
+    });
+    let mark = if res.is_ok() {
+        paint("✔", "32")
+    } else {
+        paint("✘", "31")
+    };
+    eprintln!("\r{mark} {message}");
+    res
+}
+
+///
+/// Gets the cargo root dir. Asks cargo for the workspace root, so it does not
+/// matter where the xtask crate sits. When cargo metadata is unavailable, falls
+/// back to `root_dir_from` the current directory, and then to the parent of
+/// `CARGO_MANIFEST_DIR`.
+///
+/// # Errors
+/// Fails if cargo metadata fails and neither fallback finds a directory
+///
+pub fn root_dir() -> AnyResult<PathBuf> {
+    match get_workspace_root() {
+        Ok(root) => Ok(root),
+        Err(e) => env::current_dir()
+            .ok()
+            .and_then(|dir| root_dir_from(&dir))
+            .or_else(|| {
+                Path::new(env!("CARGO_MANIFEST_DIR"))
+                    .parent()
+                    .map(Path::to_path_buf)
+            })
+            .ok_or_else(|| anyhow::Error::new(e).context("cannot find the cargo root dir")),
+    }
+}
+
+///
+/// Find the root dir above `start` without running cargo: the nearest
+/// ancestor whose `Cargo.toml` has a `[workspace]` table, or else the
+/// nearest ancestor with any `Cargo.toml`
 ///
-pub fn root_dir() -> PathBuf {
-    let mut xtask_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
-    xtask_dir.pop();
-    xtask_dir
+pub fn root_dir_from(start: &Path) -> Option<PathBuf> {
+    let manifest_dirs: Vec<&Path> = start
+        .ancestors()
+        .filter(|dir| dir.join("Cargo.toml").is_file())
+        .collect();
+    let declares_workspace = |dir: &Path| {
+        fs::read_to_string(dir.join("Cargo.toml"))
+            .ok()
+            .and_then(|manifest| manifest.parse::<DocumentMut>().ok())
+            .is_some_and(|doc| doc.contains_key("workspace"))
+    };
+    manifest_dirs
+        .iter()
+        .find(|dir| declares_workspace(dir))
+        .or_else(|| manifest_dirs.first())
+        .map(|dir| dir.to_path_buf())
 }
 /// .Return the closest anchestor containing a Cargo.toml file
 pub fn nearest_cargo_dir() -> Result<PathBuf, io::Error> {
-    let path = env::current_dir()?;
-    let path_ancestors = path.as_path().ancestors();
+    nearest_cargo_dir_from(&env::current_dir()?)
+}
 
-    for p in path_ancestors {
-        let has_cargo = !read_dir(p)?.all(|p| p.unwrap().file_name() != *"Cargo.toml");
+///
+/// Return the closest ancestor of `start`, or `start` itself, containing a Cargo.toml file
+///
+/// # Errors
+/// Fails if an ancestor directory cannot be listed or none has a Cargo.toml.
+/// Directories we may not list are skipped.
+///
+pub fn nearest_cargo_dir_from(start: &Path) -> Result<PathBuf, io::Error> {
+    for p in start.ancestors() {
+        let entries = match read_dir(p) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == ErrorKind::PermissionDenied => continue,
+            Err(e) => return Err(e),
+        };
+        // an entry that cannot be read (permissions, removed meanwhile) is skipped
+        let has_cargo = entries
+            .filter_map(Result::ok)
+            .any(|entry| entry.file_name() == "Cargo.toml");
         if has_cargo {
             return Ok(PathBuf::from(p));
         }
@@ -149,23 +1233,293 @@ pub fn nearest_cargo_dir() -> Result<PathBuf, io::Error> {
     ))
 }
 
-pub(crate) fn get_cargo_metadata() -> AnyResult<serde_json::Value> {
-    let metadata = cmd!("cargo", "metadata", "--format-version", "1").read()?;
-    let metadata: Value = serde_json::from_str(&metadata)?;
-    Ok(metadata)
+fn get_cargo_metadata() -> Result<serde_json::Value> {
+    let output = cmd!("cargo", "metadata", "--format-version", "1")
+        .stdout_capture()
+        .unchecked()
+        .run()?;
+    if !output.status.success() {
+        return Err(XtaskError::CommandFailed {
+            name: "cargo metadata".to_string(),
+            status: output.status,
+        });
+    }
+    Ok(serde_json::from_slice(&output.stdout)?)
+}
+
+/// The parts of `cargo metadata` that tasks commonly need
+#[derive(Debug, Clone, Deserialize)]
+pub struct Metadata {
+    /// root directory of the workspace
+    pub workspace_root: PathBuf,
+    /// cargo's target directory, honoring `CARGO_TARGET_DIR` and `build.target-dir`
+    pub target_directory: PathBuf,
+    /// ids of the workspace members, see [`PackageInfo::id`]
+    pub workspace_members: Vec<String>,
+    /// every package in the dependency graph, members and dependencies
+    pub packages: Vec<PackageInfo>,
+    /// the resolved dependency graph, absent with `--no-deps`
+    #[serde(default)]
+    pub resolve: Option<Resolve>,
+    /// the `[workspace.metadata]` table, if any
+    #[serde(default)]
+    pub metadata: Option<serde_json::Value>,
+}
+
+/// The resolved dependency graph of `cargo metadata`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Resolve {
+    /// one node per package in the graph
+    pub nodes: Vec<ResolveNode>,
+}
+
+/// A package in the resolved dependency graph
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ResolveNode {
+    /// package id, see [`PackageInfo::id`]
+    pub id: String,
+    /// the package's resolved dependencies
+    #[serde(default)]
+    pub deps: Vec<NodeDep>,
+}
+
+/// A resolved dependency of a [`ResolveNode`]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct NodeDep {
+    /// name of the dependency as used in code, which differs for renamed dependencies
+    pub name: String,
+    /// package id of the dependency
+    pub pkg: String,
+}
+
+/// A package as listed by `cargo metadata`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct PackageInfo {
+    /// opaque package id
+    pub id: String,
+    /// package name
+    pub name: String,
+    /// package version
+    pub version: String,
+    /// path to the package's `Cargo.toml`
+    pub manifest_path: PathBuf,
+    /// declared `rust-version`, if any
+    pub rust_version: Option<String>,
+    /// the package's build targets
+    #[serde(default)]
+    pub targets: Vec<TargetInfo>,
 }
+
+/// A build target of a package as listed by `cargo metadata`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct TargetInfo {
+    /// target name
+    pub name: String,
+    /// target kinds, e.g. `lib`, `proc-macro`, `bin` or `test`
+    pub kind: Vec<String>,
+}
+
+impl PackageInfo {
+    /// Directory holding the package's `Cargo.toml`
+    pub fn dir(&self) -> &Path {
+        self.manifest_path.parent().unwrap_or(Path::new(""))
+    }
+
+    /// Whether the package has a library target, plain or proc-macro
+    pub fn has_lib(&self) -> bool {
+        self.targets
+            .iter()
+            .flat_map(|t| &t.kind)
+            .any(|k| k == "lib" || k == "proc-macro")
+    }
+}
+
+///
+/// Run `cargo metadata` for the current workspace and parse it
+///
+/// # Errors
+/// Fails if cargo metadata fails or its output cannot be parsed
+///
+pub fn cargo_metadata() -> Result<Metadata> {
+    Ok(serde_json::from_value(get_cargo_metadata()?)?)
+}
+
+impl Metadata {
+    /// Parses the output of `cargo metadata --format-version 1`
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the JSON misses a field or has the wrong shape
+    pub fn from_json(json: &str) -> Result<Self> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// The packages that are workspace members, without their dependencies
+    pub fn members(&self) -> impl Iterator<Item = &PackageInfo> {
+        self.packages
+            .iter()
+            .filter(|p| self.workspace_members.contains(&p.id))
+    }
+}
+
+///
+/// List the packages of the current workspace, without their dependencies
+///
+/// # Errors
+/// Fails if cargo metadata fails or its output cannot be parsed
+///
+pub fn workspace_members() -> Result<Vec<PackageInfo>> {
+    Ok(cargo_metadata()?.members().cloned().collect())
+}
+
+pub(crate) fn package_dir(name: &str) -> Result<PathBuf> {
+    let metadata = cargo_metadata()?;
+    let package = metadata
+        .packages
+        .iter()
+        .find(|p| p.name == name)
+        .ok_or_else(|| XtaskError::Metadata(format!("package `{name}` not found")))?;
+    Ok(package.dir().to_path_buf())
+}
+
 /// .
 /// Returns the root of the workspace
 /// # Errors
 ///
 /// This function will return an error if the workspace could not be found.
-pub fn get_workspace_root() -> AnyResult<PathBuf> {
-    let metadata = get_cargo_metadata()?;
-    let path = metadata
-        .get("workspace_root")
-        .ok_or(anyhow!("Deserialization error"))?
-        .to_string()
-        .replace('\"', "");
-
-    Ok(PathBuf::from_str(&path)?)
+pub fn get_workspace_root() -> Result<PathBuf> {
+    Ok(cargo_metadata()?.workspace_root)
+}
+
+///
+/// Returns the cargo target directory, honoring `CARGO_TARGET_DIR` and `build.target-dir`
+///
+/// # Errors
+///
+/// This function will return an error if cargo metadata fails.
+pub fn target_dir() -> Result<PathBuf> {
+    Ok(cargo_metadata()?.target_directory)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const METADATA: &str = r#"{
+        "workspace_root": "/work/my \"quoted\" ws",
+        "target_directory": "/tmp/relocated-target",
+        "workspace_members": ["path+file:///work/ws/app#0.2.0"],
+        "packages": [
+            {
+                "id": "path+file:///work/ws/app#0.2.0",
+                "name": "app",
+                "version": "0.2.0",
+                "manifest_path": "/work/ws/app/Cargo.toml",
+                "rust_version": "1.70",
+                "targets": [
+                    {"name": "app", "kind": ["bin"]},
+                    {"name": "app", "kind": ["lib"]}
+                ]
+            },
+            {
+                "id": "registry+https://github.com/rust-lang/crates.io-index#serde@1.0.0",
+                "name": "serde",
+                "version": "1.0.0",
+                "manifest_path": "/home/u/.cargo/registry/serde-1.0.0/Cargo.toml",
+                "rust_version": null
+            }
+        ],
+        "resolve": {
+            "nodes": [
+                {
+                    "id": "path+file:///work/ws/app#0.2.0",
+                    "deps": [{
+                        "name": "serde_renamed",
+                        "pkg": "registry+https://github.com/rust-lang/crates.io-index#serde@1.0.0",
+                        "dep_kinds": [{"kind": null, "target": null}]
+                    }]
+                }
+            ]
+        },
+        "metadata": {"xtask": {"jobs": 4}}
+    }"#;
+
+    #[test]
+    fn target_dir_is_read_from_metadata() {
+        let metadata = Metadata::from_json(METADATA).unwrap();
+        assert_eq!(
+            metadata.target_directory,
+            PathBuf::from("/tmp/relocated-target")
+        );
+    }
+
+    #[test]
+    fn workspace_root_keeps_quotes() {
+        let metadata = Metadata::from_json(METADATA).unwrap();
+        assert_eq!(
+            metadata.workspace_root,
+            PathBuf::from(r#"/work/my "quoted" ws"#)
+        );
+    }
+
+    #[test]
+    fn members_leave_out_dependencies() {
+        let metadata = Metadata::from_json(METADATA).unwrap();
+        let members: Vec<_> = metadata.members().collect();
+        assert_eq!(members.len(), 1);
+        assert_eq!(members[0].name, "app");
+        assert_eq!(members[0].rust_version.as_deref(), Some("1.70"));
+        assert_eq!(members[0].dir(), Path::new("/work/ws/app"));
+        assert!(members[0].has_lib());
+        // packages listed without targets have none
+        assert!(!metadata.packages[1].has_lib());
+    }
+
+    #[test]
+    fn remove_file_if_exists_is_idempotent() {
+        let tmp = tempfile::tempdir().unwrap();
+        let file = tmp.path().join("gone.txt");
+        fs::write(&file, "").unwrap();
+
+        assert!(remove_file_if_exists(&file).unwrap());
+        assert!(!file.exists());
+        assert!(!remove_file_if_exists(&file).unwrap());
+        // a directory is not a missing file
+        assert!(remove_file_if_exists(tmp.path()).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn nearest_cargo_dir_skips_unreadable_directories() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let locked = tmp.path().join("locked");
+        fs::create_dir(&locked).unwrap();
+        fs::write(tmp.path().join("Cargo.toml"), "").unwrap();
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o000)).unwrap();
+
+        let found = nearest_cargo_dir_from(&locked);
+
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+        assert_eq!(found.unwrap(), tmp.path());
+    }
+
+    #[test]
+    fn resolve_graph_and_workspace_metadata_are_read() {
+        let metadata = Metadata::from_json(METADATA).unwrap();
+        let nodes = metadata.resolve.unwrap().nodes;
+        assert_eq!(nodes[0].deps[0].name, "serde_renamed");
+        assert_eq!(nodes[0].deps[0].pkg, metadata.packages[1].id);
+        assert_eq!(metadata.metadata.unwrap()["xtask"]["jobs"], 4);
+    }
+
+    #[test]
+    fn missing_workspace_root_is_an_error() {
+        let json = r#"{"target_directory": "/t", "workspace_members": [], "packages": []}"#;
+        assert!(matches!(
+            Metadata::from_json(json),
+            Err(XtaskError::Metadata(_))
+        ));
+    }
 }
@@ -1,11 +1,102 @@
 //!
 //! Complete xtask tasks such as `docs`, `ci` and others
 //!
-use crate::ops::{clean_files, get_clean_directory, get_workspace_root, nearest_cargo_dir};
-use anyhow::{Context, Result as AnyResult};
+use crate::config::XtaskConfig;
+use crate::error::XtaskError;
+use crate::ops::{
+    cargo_metadata, clean_files, get_clean_directory, get_workspace_root, is_dry_run, merged_flags,
+    nearest_cargo_dir, nightly_available, open_path, package_dir, plan, planned, relative_path,
+    remove_file, run_captured, run_tee, run_with_retries, source_files, status, succeeds,
+    target_dir, verbosity, with_spinner, workspace_members, write_atomic, PackageInfo, PlannedOp,
+};
+use anyhow::{bail, Context, Result as AnyResult};
 use derive_builder::Builder;
 use duct::cmd;
-use std::fs::create_dir_all;
+use serde_json::Value;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    ffi::OsString,
+    fmt,
+    fs::{self, create_dir_all},
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::{Duration, Instant},
+};
+use toml_edit::{DocumentMut, Item};
+
+/// Build a `cargo doc --no-deps` run
+#[derive(Builder)]
+#[builder(setter(into))]
+pub struct Docs {
+    /// rebuild on every change with `cargo watch`
+    /// default: on
+    #[builder(default = "true")]
+    pub watch: bool,
+
+    /// document with `--all-features`
+    /// default: off
+    #[builder(default = "false")]
+    pub all_features: bool,
+
+    /// include private items (`--document-private-items`)
+    /// default: off
+    #[builder(default = "false")]
+    pub private_items: bool,
+
+    /// open the docs in the browser (`--open`), only without `watch`
+    /// default: off
+    #[builder(default = "false")]
+    pub open: bool,
+
+    /// fail on broken intra-doc links, appending `-D rustdoc::broken_intra_doc_links`
+    /// to `RUSTDOCFLAGS`
+    /// default: off
+    #[builder(default = "false")]
+    pub deny_broken_links: bool,
+}
+
+impl Docs {
+    fn doc_args(&self) -> Vec<&'static str> {
+        let mut args = vec!["doc", "--no-deps"];
+        if self.all_features {
+            args.push("--all-features");
+        }
+        if self.private_items {
+            args.push("--document-private-items");
+        }
+        if self.open && !self.watch {
+            args.push("--open");
+        }
+        args
+    }
+}
+
+impl DocsBuilder {
+    /// Builds and runs `cargo doc`
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if run failed
+    pub fn run(&self) -> AnyResult<()> {
+        let t = self.build()?;
+        let args = t.doc_args();
+        let c = if t.watch {
+            cmd!("cargo", "watch", "-s", format!("cargo {}", args.join(" ")))
+        } else {
+            cmd("cargo", &args)
+        };
+        let c = if t.deny_broken_links {
+            c.env(
+                "RUSTDOCFLAGS",
+                merged_flags("RUSTDOCFLAGS", "-D rustdoc::broken_intra_doc_links"),
+            )
+        } else {
+            c
+        };
+        c.run()?;
+        Ok(())
+    }
+}
 
 ///
 /// Run cargo docs in watch mode
@@ -14,56 +105,470 @@ use std::fs::create_dir_all;
 /// Fails if any command fails
 ///
 pub fn docs() -> AnyResult<()> {
-    cmd!("cargo", "watch", "-s", "cargo doc --no-deps").run()?;
-    Ok(())
+    DocsBuilder::default().run()
+}
+
+/// Insert the `verbosity` flags after the cargo subcommand; nextest has its own
+fn with_verbosity<'a>(args: &[&'a str]) -> Vec<&'a str> {
+    match args.split_first() {
+        Some((&sub, rest)) if sub != "nextest" => [&[sub], verbosity().cargo_args(), rest].concat(),
+        _ => args.to_vec(),
+    }
 }
 
 /// Build a CI run
 #[derive(Builder)]
 #[builder(setter(into))]
 pub struct CI {
-    /// run with nightly
-    /// default: on
+    /// run with nightly, a shortcut for `toolchain("nightly")`
+    /// default: off
     #[builder(default = "false")]
     pub nightly: bool,
 
-    /// turn all clippy lints on: pedantic, nursery, 2018-idioms
+    /// run every cargo command with this toolchain (`+<toolchain>`), e.g. `beta` or `1.75.0`.
+    /// Takes precedence over `nightly`.
+    /// default: the active toolchain
+    #[builder(default)]
+    pub toolchain: Option<String>,
+
+    /// turn all clippy lints on: pedantic, nursery, 2018-idioms (`CLIPPY_MAX_LINTS`)
     /// default: on
     #[builder(default = "true")]
     pub clippy_max: bool,
+
+    /// lint flags passed to clippy instead of `CLIPPY_MAX_LINTS`, e.g. `["-W", "clippy::pedantic"]`.
+    /// `-D warnings` is still added unless these flags set the `warnings` level themselves.
+    /// default: none
+    #[builder(default)]
+    pub clippy_lints: Vec<String>,
+
+    /// also verify docs and examples with `--all-features`
+    /// default: off
+    #[builder(default = "false")]
+    pub check_examples: bool,
+
+    /// number of parallel jobs for clippy and tests
+    /// default: cargo's default
+    #[builder(default)]
+    pub jobs: Option<usize>,
+
+    /// treat compiler warnings as errors in tests and the examples build, appending
+    /// `-D warnings` to `RUSTFLAGS`, and in doc tests, appending it to `RUSTDOCFLAGS`
+    /// default: off
+    #[builder(default = "false")]
+    pub deny_warnings: bool,
+
+    /// workspace crates to leave out of clippy and tests. When set, clippy and
+    /// tests run with `--workspace`
+    /// default: xtask
+    #[builder(default = "vec![\"xtask\".to_string()]")]
+    pub exclude: Vec<String>,
+
+    /// keep running the remaining test targets after one fails (`--no-fail-fast`)
+    /// default: off
+    #[builder(default = "false")]
+    pub no_fail_fast: bool,
+
+    /// extra targets to run clippy for, e.g. `wasm32-unknown-unknown`.
+    /// Clippy also runs for the host; tests run on the host only.
+    /// default: none
+    #[builder(default)]
+    pub targets: Vec<String>,
+
+    /// install missing `targets` with `rustup target add`
+    /// default: off
+    #[builder(default = "false")]
+    pub install_targets: bool,
+
+    /// run tests with `cargo nextest run` instead of `cargo test`.
+    /// Doc tests still run with `cargo test --doc`.
+    /// default: off
+    #[builder(default = "false")]
+    pub nextest: bool,
+
+    /// run the `cargo fmt --check` step
+    /// default: on
+    #[builder(default = "true")]
+    pub run_fmt: bool,
+
+    /// run the clippy step, including the extra `targets`
+    /// default: on
+    #[builder(default = "true")]
+    pub run_clippy: bool,
+
+    /// run the test step
+    /// default: on
+    #[builder(default = "true")]
+    pub run_tests: bool,
+
+    /// run the doc test step
+    /// default: on
+    #[builder(default = "true")]
+    pub run_doc_tests: bool,
+
+    /// stop at the first failing step. When off, all steps run and the
+    /// error lists every step that failed
+    /// default: on
+    #[builder(default = "true")]
+    pub fail_fast: bool,
+
+    /// features to enable for clippy and tests (`--features`)
+    /// default: none
+    #[builder(default)]
+    pub features: Vec<String>,
+
+    /// enable all features for clippy and tests (`--all-features`). Cannot be combined with `features`.
+    /// default: off
+    #[builder(default = "false")]
+    pub all_features: bool,
+
+    /// disable the default features for clippy and tests (`--no-default-features`)
+    /// default: off
+    #[builder(default = "false")]
+    pub no_default_features: bool,
 }
 
 impl CIBuilder {
+    /// Applies the defaults set in `[workspace.metadata.xtask]`
+    pub fn with_config(&mut self, config: &XtaskConfig) -> &mut Self {
+        if let Some(nightly) = config.nightly {
+            self.nightly(nightly);
+        }
+        if let Some(toolchain) = &config.toolchain {
+            self.toolchain(toolchain.clone());
+        }
+        if let Some(clippy_max) = config.clippy_max {
+            self.clippy_max(clippy_max);
+        }
+        if let Some(jobs) = config.jobs {
+            self.jobs(jobs);
+        }
+        if let Some(clippy_lints) = &config.clippy_lints {
+            self.clippy_lints(clippy_lints.clone());
+        }
+        self
+    }
+
     /// Runs this builder
     ///
     /// # Errors
     ///
     /// This function will return an error if run failed
     pub fn run(&self) -> AnyResult<()> {
+        let no_fail_fast = self.build()?.no_fail_fast;
+        let report = self.run_report()?;
+        let failures: Vec<String> = report
+            .failed()
+            .map(|step| {
+                let exit = match step.code {
+                    Some(code) => format!("exit code {code}"),
+                    None => "terminated".to_string(),
+                };
+                // with `--no-fail-fast` every test target ran, so several may have failed
+                if no_fail_fast && matches!(step.name.as_str(), "tests" | "doc tests") {
+                    format!("`{}` ({exit}, one or more test targets failed)", step.name)
+                } else {
+                    format!("`{}` ({exit})", step.name)
+                }
+            })
+            .collect();
+        match failures.as_slice() {
+            [] => Ok(()),
+            [failure] => bail!("CI step {failure} failed, see the output above"),
+            _ => bail!(
+                "{} CI steps failed, see the output above:\n{}",
+                failures.len(),
+                failures.join("\n")
+            ),
+        }
+    }
+
+    /// Runs this builder and reports the outcome and duration of every step.
+    /// A failing step does not make this an error; with `fail_fast` it ends the run.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the builder is invalid or a command cannot be started
+    pub fn run_report(&self) -> AnyResult<CIReport> {
         let t = self.build()?;
-        let mut check_args = vec!["fmt", "--all", "--", "--check"];
-        if t.nightly {
-            check_args.insert(0, "+nightly");
+        let steps = t.steps()?;
+        let toolchain = t.toolchain_arg();
+        let cargo = |args: &[&str]| {
+            cmd(
+                "cargo",
+                toolchain
+                    .iter()
+                    .map(String::as_str)
+                    .chain(with_verbosity(args)),
+            )
+        };
+
+        if t.install_targets {
+            install_missing_targets(&t.targets, t.toolchain_name())?;
+        }
+        if t.run_tests && t.nextest && !succeeds(&cargo(&["nextest", "--version"])) {
+            bail!("cargo-nextest is not installed, run `cargo install cargo-nextest --locked`");
         }
 
-        let mut clippy_args = vec!["clippy", "--", "-D", "warnings"];
-        if t.clippy_max {
-            clippy_args.extend([
-                "-W",
-                "clippy::pedantic",
-                "-W",
-                "clippy::nursery",
-                "-W",
-                "rust-2018-idioms",
-            ]);
+        let mut report = CIReport::default();
+        for step in steps {
+            let args: Vec<&str> = step.args.iter().map(String::as_str).collect();
+            let c = cargo(&args).unchecked();
+            let c = match step.warnings_var {
+                Some(var) if t.deny_warnings => c.env(var, merged_flags(var, "-D warnings")),
+                _ => c,
+            };
+            let command = toolchain
+                .iter()
+                .map(String::as_str)
+                .chain(with_verbosity(&args))
+                .fold("cargo".to_string(), |cmd, arg| cmd + " " + arg);
+            let started = Instant::now();
+            let status = c.run()?.status;
+            report.steps.push(StepResult {
+                name: step.name,
+                command,
+                duration: started.elapsed(),
+                success: status.success(),
+                code: status.code(),
+            });
+            if t.fail_fast && !status.success() {
+                break;
+            }
         }
+        Ok(report)
+    }
+}
 
-        cmd("cargo", check_args.as_slice()).run()?;
-        cmd("cargo", clippy_args.as_slice()).run()?;
-        cmd!("cargo", "test").run()?;
-        cmd!("cargo", "test", "--doc").run()?;
-        Ok(())
+/// A CI step before it runs
+#[derive(Debug)]
+struct PlannedStep {
+    /// step name, e.g. `fmt` or `clippy wasm32-unknown-unknown`
+    name: String,
+    /// arguments after `cargo +<toolchain>`
+    args: Vec<String>,
+    /// the flags variable `deny_warnings` appends `-D warnings` to, if it applies
+    warnings_var: Option<&'static str>,
+}
+
+impl CI {
+    /// The toolchain to run with, e.g. `nightly`
+    fn toolchain_name(&self) -> Option<&str> {
+        self.toolchain
+            .as_deref()
+            .or_else(|| self.nightly.then_some("nightly"))
+    }
+
+    /// The `+<toolchain>` argument, if any
+    fn toolchain_arg(&self) -> Option<String> {
+        self.toolchain_name().map(|channel| format!("+{channel}"))
+    }
+
+    /// The enabled steps, in the order they run
+    fn steps(&self) -> AnyResult<Vec<PlannedStep>> {
+        let jobs = self.jobs.map(|j| j.to_string());
+        let jobs_args: Vec<&str> = jobs.iter().flat_map(|j| ["--jobs", j]).collect();
+        let workspace = if self.exclude.is_empty() {
+            vec![]
+        } else {
+            workspace_args(&self.exclude)
+        };
+        if self.all_features && !self.features.is_empty() {
+            bail!("`all_features` cannot be combined with a `features` list");
+        }
+        let features = self.features.join(",");
+        let mut feature_args = vec![];
+        if !features.is_empty() {
+            feature_args.extend(["--features", features.as_str()]);
+        }
+        if self.all_features {
+            feature_args.push("--all-features");
+        }
+        if self.no_default_features {
+            feature_args.push("--no-default-features");
+        }
+        let cargo_args = [
+            workspace.as_slice(),
+            jobs_args.as_slice(),
+            feature_args.as_slice(),
+        ]
+        .concat();
+
+        let clippy_lints = clippy_lint_args(self.clippy_max, &self.clippy_lints);
+
+        let no_fail_fast: &[&str] = if self.no_fail_fast {
+            &["--no-fail-fast"]
+        } else {
+            &[]
+        };
+        // nextest takes the build parallelism as `--build-jobs`, `--jobs` sets test threads
+        let nextest_jobs: Vec<&str> = jobs.iter().flat_map(|j| ["--build-jobs", j]).collect();
+
+        let mut steps = vec![];
+        let mut push = |name: String, args: &[&str], warnings_var: Option<&'static str>| {
+            steps.push(PlannedStep {
+                name,
+                args: args.iter().map(ToString::to_string).collect(),
+                warnings_var,
+            });
+        };
+        if self.run_fmt {
+            push("fmt".to_string(), &["fmt", "--all", "--", "--check"], None);
+        }
+        if self.run_clippy {
+            push(
+                "clippy".to_string(),
+                &[&["clippy"], cargo_args.as_slice(), clippy_lints.as_slice()].concat(),
+                None,
+            );
+            for target in &self.targets {
+                push(
+                    format!("clippy {target}"),
+                    &[
+                        &["clippy", "--target", target],
+                        cargo_args.as_slice(),
+                        clippy_lints.as_slice(),
+                    ]
+                    .concat(),
+                    None,
+                );
+            }
+        }
+        if self.run_tests {
+            let args = if self.nextest {
+                [
+                    &["nextest", "run"],
+                    workspace.as_slice(),
+                    nextest_jobs.as_slice(),
+                    feature_args.as_slice(),
+                    no_fail_fast,
+                ]
+                .concat()
+            } else {
+                [&["test"], cargo_args.as_slice(), no_fail_fast].concat()
+            };
+            push("tests".to_string(), &args, Some("RUSTFLAGS"));
+        }
+        if self.run_doc_tests {
+            push(
+                "doc tests".to_string(),
+                &[&["test", "--doc"], cargo_args.as_slice(), no_fail_fast].concat(),
+                Some("RUSTDOCFLAGS"),
+            );
+        }
+        if self.check_examples {
+            push(
+                "examples doc tests".to_string(),
+                &["test", "--doc", "--all-features"],
+                None,
+            );
+            push(
+                "examples build".to_string(),
+                &["build", "--examples", "--all-features"],
+                Some("RUSTFLAGS"),
+            );
+        }
+        Ok(steps)
+    }
+}
+
+/// Outcome of a CI run, see `CIBuilder::run_report`
+#[derive(Debug, Clone, Default)]
+pub struct CIReport {
+    /// the steps that ran, in order
+    pub steps: Vec<StepResult>,
+}
+
+impl CIReport {
+    /// Whether every step that ran succeeded
+    pub fn success(&self) -> bool {
+        self.steps.iter().all(|step| step.success)
+    }
+
+    /// The steps that failed
+    pub fn failed(&self) -> impl Iterator<Item = &StepResult> {
+        self.steps.iter().filter(|step| !step.success)
+    }
+
+    /// Total time spent in all steps
+    pub fn duration(&self) -> Duration {
+        self.steps.iter().map(|step| step.duration).sum()
+    }
+}
+
+/// Outcome of a single CI step
+#[derive(Debug, Clone)]
+pub struct StepResult {
+    /// step name, e.g. `fmt`, `clippy` or `tests`
+    pub name: String,
+
+    /// the command line that ran
+    pub command: String,
+
+    /// how long the command took
+    pub duration: Duration,
+
+    /// whether the command exited successfully
+    pub success: bool,
+
+    /// exit code, `None` if the command was terminated by a signal
+    pub code: Option<i32>,
+}
+
+/// Lints turned on by `CI::clippy_max`
+pub const CLIPPY_MAX_LINTS: &[&str] = &[
+    "-W",
+    "clippy::pedantic",
+    "-W",
+    "clippy::nursery",
+    "-W",
+    "rust-2018-idioms",
+];
+
+/// Whether a clippy argument names the `warnings` group, as in `-A warnings`,
+/// `-Awarnings` or `--allow=warnings`
+fn sets_warnings_level(arg: &str) -> bool {
+    arg.rsplit('=')
+        .next()
+        .is_some_and(|a| a.trim_start_matches(['-', 'A', 'W', 'D', 'F']) == "warnings")
+}
+
+/// Arguments after `cargo clippy`: `-- -D warnings` followed by the lint flags
+fn clippy_lint_args(clippy_max: bool, custom: &[String]) -> Vec<&str> {
+    let mut args = vec!["--"];
+    if !custom.iter().any(|arg| sets_warnings_level(arg)) {
+        args.extend(["-D", "warnings"]);
+    }
+    if !custom.is_empty() {
+        args.extend(custom.iter().map(String::as_str));
+    } else if clippy_max {
+        args.extend(CLIPPY_MAX_LINTS);
+    }
+    args
+}
+
+/// Add the `targets` missing from `toolchain`, or from the active toolchain when `None`
+fn install_missing_targets(targets: &[String], toolchain: Option<&str>) -> AnyResult<()> {
+    let toolchain_args: Vec<&str> = toolchain.iter().flat_map(|t| ["--toolchain", t]).collect();
+    let installed = cmd(
+        "rustup",
+        [
+            &["target", "list", "--installed"],
+            toolchain_args.as_slice(),
+        ]
+        .concat(),
+    )
+    .read()?;
+    for target in targets {
+        if !installed.lines().any(|line| line.trim() == target) {
+            cmd(
+                "rustup",
+                [&["target", "add", target], toolchain_args.as_slice()].concat(),
+            )
+            .run()?;
+        }
     }
+    Ok(())
 }
 
 ///
@@ -76,201 +581,3063 @@ pub fn ci() -> AnyResult<()> {
     CIBuilder::default().run()
 }
 
-fn cobertura_total_coverage(filename: &str) -> AnyResult<()> {
-    let total_coverage: f32 = cmd!(
-        "xmllint",
-        "--xpath",
-        "string(//coverage/@line-rate)",
-        filename
-    )
-    .read()?
-    .parse()?;
-    println!("Coverage: {:.2}%", 100f32 * total_coverage);
+///
+/// Run only unit tests: the tests inside library and binary targets
+///
+/// # Errors
+/// Fails if any test fails
+///
+pub fn test_unit() -> AnyResult<()> {
+    cmd!("cargo", "test", "--lib", "--bins").run()?;
     Ok(())
 }
 
 ///
-/// Run coverage
+/// Run only integration tests, the targets under `tests/`
 ///
 /// # Errors
-/// Fails if any command fails
+/// Fails if any test fails
 ///
-pub fn coverage(fmt: &str) -> AnyResult<()> {
-    let project_root = nearest_cargo_dir()?;
-    let workspace_root = get_workspace_root()?;
-
-    let coverage_dir = project_root.join("coverage");
-    get_clean_directory(&coverage_dir)?;
-
-    let profile_files = coverage_dir.join("cargo-test-%p-%m.profraw");
-    let binary_folder = workspace_root.join("target");
-    let source_dir = project_root.join("src");
-
-    cmd!("cargo", "test", "--all-features")
-        .env("CARGO_TARGET_DIR", binary_folder.clone())
-        .env("RUSTFLAGS", "-Cinstrument-coverage")
-        .env("LLVM_PROFILE_FILE", profile_files.as_path())
-        .run()?;
+pub fn test_integration() -> AnyResult<()> {
+    cmd!("cargo", "test", "--test", "*").run()?;
+    Ok(())
+}
 
-    println!("ok.");
+fn parse_test_time(line: &str) -> Option<(&str, f64)> {
+    let (name, result) = line.strip_prefix("test ")?.split_once(" ... ")?;
+    let secs: f64 = result
+        .rsplit_once('<')?
+        .1
+        .trim_end()
+        .strip_suffix("s>")?
+        .parse()
+        .ok()?;
+    Some((name, secs * 1000.0))
+}
 
-    if fmt == "profraw" {
-        return Ok(());
+///
+/// Run the test suite with libtest's `--report-time` (requires nightly) and
+/// list the tests slower than `threshold_ms`, slowest first
+///
+/// # Errors
+/// Fails if nightly is not installed or any test fails
+///
+pub fn slow_tests(threshold_ms: u64) -> AnyResult<()> {
+    if !nightly_available() {
+        bail!("slow-tests requires a nightly toolchain, run `rustup toolchain install nightly`");
     }
-    println!("=== generating report ===");
-    let output_folder = match fmt {
-        "html" | "lcov" | "cobertura" | "covdir" => Ok(coverage_dir.clone()),
-
-        _ => Err(anyhow::Error::msg(format!(
-            "Please provide a valid output file format found : {fmt}"
-        ))),
-    }?;
-
-    create_dir_all(output_folder.clone())?;
-    cmd!(
-        "grcov",
-        coverage_dir,
-        "--binary-path",
-        binary_folder,
-        "--source-dir",
-        source_dir,
-        "--output-types",
-        fmt,
-        "--branch",
-        "--ignore-not-existing",
-        "--ignore",
-        "../*",
-        "--ignore",
-        "/*",
-        "--ignore",
-        "xtask/*",
-        "-o",
-        output_folder,
-    )
-    .run()?;
-    println!("ok.");
-
-    println!("=== cleaning up ===");
-    clean_files("**/*.profraw")?;
-    //clean_files("**/*.profraw")?;
-    println!("ok.");
+    let (code, output) = run_tee(
+        "cargo",
+        [
+            "+nightly",
+            "test",
+            "--",
+            "-Z",
+            "unstable-options",
+            "--report-time",
+        ],
+    )?;
 
+    #[allow(clippy::cast_precision_loss)]
+    let threshold = threshold_ms as f64;
+    let mut slow: Vec<_> = output
+        .lines()
+        .filter_map(parse_test_time)
+        .filter(|(_, ms)| *ms >= threshold)
+        .collect();
+    slow.sort_by(|a, b| b.1.total_cmp(&a.1));
+    status(&format!("=== tests slower than {threshold_ms}ms ==="), "1");
+    for (name, ms) in &slow {
+        println!("{ms:>10.0}ms  {name}");
+    }
+    if code != 0 {
+        bail!("tests failed with exit code {code}");
+    }
     Ok(())
 }
 
-/// Build a powerset test
-#[derive(Builder)]
-#[builder(setter(into))]
-pub struct Powerset {
-    /// powerset depth
-    #[builder(default = "2")]
-    pub depth: i32,
-
-    /// dont run with no feature at all
-    #[builder(default = "false")]
-    pub exclude_no_default_features: bool,
+///
+/// Run doc tests and build examples with `--all-features`, catching
+/// feature-gated items that break docs or examples
+///
+/// # Errors
+/// Fails if any command fails
+///
+pub fn check_examples() -> AnyResult<()> {
+    cmd!("cargo", "test", "--doc", "--all-features").run()?;
+    cmd!("cargo", "build", "--examples", "--all-features").run()?;
+    Ok(())
 }
 
-impl PowersetBuilder {
-    /// Builds and runs a powerset test
-    ///
-    /// # Errors
-    ///
-    /// This function will return an error if run failed
-    pub fn run(&self) -> AnyResult<()> {
-        let t = self.build()?;
-        let depth = format!("{}", t.depth);
-        let mut common = vec![
-            "--workspace",
-            "--exclude",
-            "xtask",
-            "--feature-powerset",
-            "--depth",
-            &depth,
-        ];
-        if t.exclude_no_default_features {
-            common.push("--exclude-no-default-features");
-        }
-        cmd(
-            "cargo",
-            &[
-                &["hack", "clippy"],
-                common.as_slice(),
-                &["--", "-D", "warnings"],
-            ]
-            .concat(),
-        )
-        .run()?;
-        cmd("cargo", &[&["hack"], common.as_slice(), &["test"]].concat()).run()?;
-        cmd(
-            "cargo",
-            &[&["hack", "test"], common.as_slice(), &["--doc"]].concat(),
-        )
-        .run()?;
-        Ok(())
+///
+/// Read the total line coverage, in percent, from a cobertura report
+///
+/// # Errors
+/// Fails if the report cannot be read or parsed
+///
+pub fn cobertura_coverage_percent(filename: &str) -> AnyResult<f32> {
+    let xml = fs::read_to_string(filename)?;
+    // grcov and cargo-llvm-cov both emit a DOCTYPE
+    let options = roxmltree::ParsingOptions {
+        allow_dtd: true,
+        ..roxmltree::ParsingOptions::default()
+    };
+    let doc = roxmltree::Document::parse_with_options(&xml, options)?;
+    let root = doc.root_element();
+    if !root.has_tag_name("coverage") {
+        bail!("{filename} is not a cobertura report");
     }
+    let line_rate: f32 = root
+        .attribute("line-rate")
+        .context("cobertura report has no line-rate")?
+        .parse()?;
+    Ok(100f32 * line_rate)
 }
 
 ///
-/// Perform a CI build with powerset of features
+/// Read the total line coverage, in percent, from a grcov covdir report
 ///
 /// # Errors
-/// Errors if one of the commands failed
+/// Fails if the report cannot be read or parsed
 ///
-pub fn powerset() -> AnyResult<()> {
-    PowersetBuilder::default().run()
+pub fn covdir_coverage_percent(filename: &str) -> AnyResult<f32> {
+    let report: Value = serde_json::from_str(&fs::read_to_string(filename)?)?;
+    let percent = report
+        .get("coveragePercent")
+        .and_then(Value::as_f64)
+        .context("covdir report has no coveragePercent")?;
+    #[allow(clippy::cast_possible_truncation)]
+    Ok(percent as f32)
 }
 
 ///
-/// Show biggest crates in release build
+/// Read the total line coverage, in percent, from an lcov tracefile by summing
+/// the `LH` (lines hit) and `LF` (lines found) records of all files
 ///
 /// # Errors
-/// Errors if the command failed
+/// Fails if the report cannot be read, is malformed, or has no lines
 ///
-pub fn bloat_deps(package: &str) -> AnyResult<()> {
-    cmd!("cargo", "bloat", "--release", "--crates", "-p", package).run()?;
+pub fn lcov_coverage_percent(filename: &str) -> AnyResult<f32> {
+    let (mut hit, mut found) = (0u64, 0u64);
+    for line in fs::read_to_string(filename)?.lines() {
+        if let Some(n) = line.strip_prefix("LH:") {
+            hit += n.trim().parse::<u64>()?;
+        } else if let Some(n) = line.strip_prefix("LF:") {
+            found += n.trim().parse::<u64>()?;
+        }
+    }
+    if found == 0 {
+        bail!("lcov report {filename} has no instrumented lines");
+    }
+    #[allow(clippy::cast_precision_loss)]
+    Ok(100f32 * hit as f32 / found as f32)
+}
+
+/// Total line coverage of the report `fmt` wrote to `coverage_dir`
+fn report_coverage_percent(fmt: CoverageFormat, coverage_dir: &Path) -> AnyResult<f32> {
+    let report = |name: &str| coverage_dir.join(name).to_string_lossy().into_owned();
+    match fmt {
+        CoverageFormat::Cobertura => cobertura_coverage_percent(&report("cobertura.xml")),
+        CoverageFormat::Covdir => covdir_coverage_percent(&report("covdir")),
+        CoverageFormat::Lcov => lcov_coverage_percent(&report("lcov")),
+        CoverageFormat::Html | CoverageFormat::Profraw => {
+            bail!(
+                "cannot read the total coverage from a {fmt} report, use lcov, cobertura or covdir"
+            )
+        }
+    }
+}
+
+fn cobertura_total_coverage(filename: &str) -> AnyResult<()> {
+    println!("Coverage: {:.2}%", cobertura_coverage_percent(filename)?);
     Ok(())
 }
 
+fn current_coverage_percent() -> AnyResult<f32> {
+    coverage_percent_in(&nearest_cargo_dir()?)
+}
+
+/// Total coverage of the project in `project_root`
+fn coverage_percent_in(project_root: &Path) -> AnyResult<f32> {
+    CoverageBuilder::default()
+        .fmt(CoverageFormat::Covdir)
+        .build()?
+        .generate(project_root)?;
+    let report = project_root.join("coverage").join("covdir");
+    covdir_coverage_percent(&report.to_string_lossy())
+}
+
 ///
-/// Show crate build times
+/// Run coverage and store the total line coverage in `baseline_file`,
+/// for later comparison with `coverage_diff`
 ///
 /// # Errors
-/// Errors if the command failed
+/// Fails if coverage or writing the baseline fails
 ///
-pub fn bloat_time(package: &str) -> AnyResult<()> {
-    cmd!("cargo", "bloat", "--time", "-j", "1", "-p", package).run()?;
+pub fn save_coverage_baseline(baseline_file: &str) -> AnyResult<()> {
+    let total = current_coverage_percent()?;
+    write_atomic(baseline_file, format!("{total:.2}\n").as_bytes())?;
+    println!("Coverage baseline: {total:.2}%");
     Ok(())
 }
 
 ///
-/// Watch changes and after every change: `cargo check`, followed by `cargo test`
-/// If `cargo check` fails, tests will not run.
+/// Run coverage and compare the total against the one stored in `baseline_file`.
+/// `tolerance` is the drop, in percentage points, that is still accepted.
 ///
 /// # Errors
-/// Errors if the command failed
+/// Fails if coverage dropped by more than `tolerance`, or if any command fails
 ///
-pub fn dev() -> AnyResult<()> {
-    cmd!("cargo", "watch", "-x", "check", "-x", "test").run()?;
+pub fn coverage_diff(baseline_file: &str, tolerance: f32) -> AnyResult<()> {
+    let baseline: f32 = fs::read_to_string(baseline_file)
+        .with_context(|| format!("cannot read coverage baseline {baseline_file}"))?
+        .trim()
+        .parse()
+        .with_context(|| format!("invalid coverage baseline in {baseline_file}"))?;
+    let total = current_coverage_percent()?;
+    let delta = total - baseline;
+    println!("Coverage: {total:.2}% (baseline {baseline:.2}%, {delta:+.2}%)");
+    if delta < -tolerance {
+        bail!(
+            "coverage dropped by {:.2}%, more than the allowed {tolerance:.2}%",
+            -delta
+        );
+    }
     Ok(())
 }
 
+/// Total coverage of `commit`, computed in a temporary git worktree that shares
+/// the current target directory
+fn coverage_percent_at(commit: &str) -> AnyResult<f32> {
+    let toplevel = PathBuf::from(cmd!("git", "rev-parse", "--show-toplevel").read()?);
+    let project_root = nearest_cargo_dir()?;
+    let worktree = target_dir()?.join("xtask-coverage-worktree");
+    if worktree.exists() {
+        cmd!("git", "worktree", "remove", "--force", &worktree)
+            .unchecked()
+            .run()?;
+    }
+    cmd!("git", "worktree", "add", "--detach", &worktree, commit).run()?;
+
+    let inner = project_root
+        .strip_prefix(&toplevel)
+        .unwrap_or(Path::new(""));
+    let res = coverage_percent_in(&worktree.join(inner));
+    let removed = cmd!("git", "worktree", "remove", "--force", &worktree).run();
+    // a failed coverage run is the error worth reporting
+    let percent = res?;
+    removed?;
+    Ok(percent)
+}
+
 ///
-/// Instal cargo tools
+/// Run coverage and compare the total against the one of git revision `base_ref`,
+/// e.g. `origin/main`. The base coverage is computed in a temporary worktree once
+/// per commit and kept in `<target dir>/xtask-coverage-baseline/<commit>`. With a
+/// `tolerance`, a drop of more percentage points is an error.
 ///
 /// # Errors
-/// Errors if one of the commands failed
+/// Fails if coverage dropped by more than `tolerance`, or if any command fails
 ///
-pub fn install() -> AnyResult<()> {
-    cmd!("cargo", "install", "cargo-watch").run()?;
-    cmd!("cargo", "install", "cargo-hack").run()?;
-    cmd!("cargo", "install", "cargo-bloat").run()?;
-    cmd!("rustup", "component", "add", "llvm-tools-preview").run()?;
-    cmd!("cargo", "install", "grcov").run()?;
+pub fn coverage_diff_ref(base_ref: &str, tolerance: Option<f32>) -> AnyResult<()> {
+    let commit = cmd!(
+        "git",
+        "rev-parse",
+        "--verify",
+        format!("{base_ref}^{{commit}}")
+    )
+    .read()?;
+    let baseline_dir = target_dir()?.join("xtask-coverage-baseline");
+    let baseline_file = baseline_dir.join(&commit);
+    let baseline = match fs::read_to_string(&baseline_file) {
+        Ok(stored) => stored
+            .trim()
+            .parse()
+            .with_context(|| format!("invalid coverage baseline in {}", baseline_file.display()))?,
+        Err(_) => {
+            let baseline = coverage_percent_at(&commit)?;
+            create_dir_all(&baseline_dir)?;
+            write_atomic(&baseline_file, format!("{baseline:.2}\n").as_bytes())?;
+            baseline
+        }
+    };
+    let total = current_coverage_percent()?;
+
+    let delta = total - baseline;
+    println!("Coverage: {total:.2}% ({base_ref} {baseline:.2}%, {delta:+.2}%)");
+    if let Some(tolerance) = tolerance {
+        if delta < -tolerance {
+            bail!(
+                "coverage dropped by {:.2}% against {base_ref}, more than the allowed {tolerance:.2}%",
+                -delta
+            );
+        }
+    }
     Ok(())
 }
 
-/// Set up a main for your xtask. Uses clap.
+/// Output format of a coverage run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoverageFormat {
+    /// html report in `coverage/html`
+    Html,
+    /// `coverage/lcov`
+    Lcov,
+    /// `coverage/cobertura.xml`
+    Cobertura,
+    /// json summary in `coverage/covdir`
+    Covdir,
+    /// raw profiles only, no report
+    Profraw,
+}
+
+impl CoverageFormat {
+    /// All formats, in the order shown by the CLI
+    pub const ALL: [Self; 5] = [
+        Self::Html,
+        Self::Lcov,
+        Self::Cobertura,
+        Self::Covdir,
+        Self::Profraw,
+    ];
+
+    /// Name of the format, as accepted by `from_str` and grcov's `--output-types`
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Html => "html",
+            Self::Lcov => "lcov",
+            Self::Cobertura => "cobertura",
+            Self::Covdir => "covdir",
+            Self::Profraw => "profraw",
+        }
+    }
+}
+
+impl FromStr for CoverageFormat {
+    type Err = XtaskError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|f| f.as_str() == s)
+            .ok_or_else(|| {
+                XtaskError::InvalidArgument(format!(
+                    "unknown coverage format `{s}`, expected one of: {}",
+                    Self::ALL.map(Self::as_str).join(", ")
+                ))
+            })
+    }
+}
+
+impl TryFrom<&str> for CoverageFormat {
+    type Error = XtaskError;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for CoverageFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Tool that instruments the tests and renders the coverage report
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CoverageBackend {
+    /// `cargo test` with `-Cinstrument-coverage`, report by grcov
+    #[default]
+    Grcov,
+    /// `cargo llvm-cov`, which handles instrumentation and profile merging itself
+    LlvmCov,
+}
+
+impl FromStr for CoverageBackend {
+    type Err = XtaskError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "grcov" => Ok(Self::Grcov),
+            "llvm-cov" => Ok(Self::LlvmCov),
+            _ => Err(XtaskError::InvalidArgument(format!(
+                "unknown coverage backend `{s}`, expected grcov or llvm-cov"
+            ))),
+        }
+    }
+}
+
+/// Build a coverage run
+#[derive(Builder)]
+#[builder(setter(into))]
+pub struct Coverage {
+    /// output format
+    pub fmt: CoverageFormat,
+
+    /// number of parallel jobs for the instrumented test build
+    /// default: cargo's default
+    #[builder(default)]
+    pub jobs: Option<usize>,
+
+    /// flags added to `RUSTFLAGS` next to `-Cinstrument-coverage`, e.g. `--cfg coverage`
+    /// default: none
+    #[builder(default)]
+    pub extra_rustflags: Vec<String>,
+
+    /// only measure this workspace package (`-p`), reporting on its `src/`
+    /// default: the current crate
+    #[builder(default)]
+    pub package: Option<String>,
+
+    /// coverage tool to use. `LlvmCov` supports html, lcov and cobertura;
+    /// profraw keeps the raw profiles (`--no-report`)
+    /// default: grcov
+    #[builder(default)]
+    pub backend: CoverageBackend,
+
+    /// fail when total line coverage, in percent, is below this.
+    /// Needs a lcov, cobertura or covdir report.
+    /// default: no minimum
+    #[builder(default)]
+    pub min_coverage: Option<f32>,
+
+    /// open `coverage/html/index.html` in the browser when the format is html
+    /// default: off
+    #[builder(default = "false")]
+    pub open: bool,
+}
+
+impl CoverageBuilder {
+    /// Applies the defaults set in `[workspace.metadata.xtask]`
+    pub fn with_config(&mut self, config: &XtaskConfig) -> &mut Self {
+        if let Some(fmt) = config.coverage_format {
+            self.fmt(fmt);
+        }
+        if let Some(jobs) = config.jobs {
+            self.jobs(jobs);
+        }
+        if let Some(min_coverage) = config.min_coverage {
+            self.min_coverage(min_coverage);
+        }
+        self
+    }
+
+    /// Builds and runs coverage
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if run failed
+    pub fn run(&self) -> AnyResult<()> {
+        let t = self.build()?;
+        if t.min_coverage.is_some()
+            && matches!(t.fmt, CoverageFormat::Html | CoverageFormat::Profraw)
+        {
+            bail!(
+                "min_coverage needs a lcov, cobertura or covdir report, not {}",
+                t.fmt
+            );
+        }
+        let project_root = nearest_cargo_dir()?;
+        t.generate(&project_root)?;
+
+        if t.open {
+            if t.fmt == CoverageFormat::Html {
+                open_path(
+                    project_root
+                        .join("coverage")
+                        .join("html")
+                        .join("index.html"),
+                )?;
+            } else {
+                println!(
+                    "not opening the {} report, only html reports can be opened",
+                    t.fmt
+                );
+            }
+        }
+
+        if let Some(min_coverage) = t.min_coverage {
+            let total = report_coverage_percent(t.fmt, &project_root.join("coverage"))?;
+            println!("Coverage: {total:.2}% (minimum {min_coverage:.2}%)");
+            if total < min_coverage {
+                bail!("coverage {total:.2}% is below the required {min_coverage:.2}%");
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Coverage {
+    /// Runs the instrumented tests and writes the report to `project_root/coverage`
+    fn generate(&self, project_root: &Path) -> AnyResult<()> {
+        let fmt = self.fmt;
+        let coverage_dir = project_root.join("coverage");
+        get_clean_directory(&coverage_dir)?;
+        if self.backend == CoverageBackend::LlvmCov {
+            return run_llvm_cov(self, project_root, &coverage_dir);
+        }
+
+        let profile_files = coverage_dir.join("cargo-test-%p-%m.profraw");
+        let binary_folder = target_dir()?;
+        let source_dir = match &self.package {
+            Some(package) => package_dir(package)?.join("src"),
+            None => project_root.join("src"),
+        };
+
+        let rustflags = [
+            &["-Cinstrument-coverage".to_string()],
+            self.extra_rustflags.as_slice(),
+        ]
+        .concat()
+        .join(" ");
+        let mut test_args = vec!["test".to_string(), "--all-features".to_string()];
+        test_args.extend(verbosity().cargo_args().iter().map(ToString::to_string));
+        if let Some(jobs) = self.jobs {
+            test_args.extend(["--jobs".to_string(), jobs.to_string()]);
+        }
+        if let Some(package) = &self.package {
+            test_args.extend(["-p".to_string(), package.clone()]);
+        }
+        cmd("cargo", &test_args)
+            .dir(project_root)
+            .env("CARGO_TARGET_DIR", binary_folder.clone())
+            .env("RUSTFLAGS", merged_flags("RUSTFLAGS", &rustflags))
+            .env("LLVM_PROFILE_FILE", profile_files.as_path())
+            .run()?;
+
+        status("ok.", "32");
+
+        if fmt == CoverageFormat::Profraw {
+            return Ok(());
+        }
+        let output_folder = coverage_dir.clone();
+
+        with_spinner("generating report", || -> AnyResult<()> {
+            create_dir_all(output_folder.clone())?;
+            cmd!(
+                "grcov",
+                coverage_dir,
+                "--binary-path",
+                binary_folder,
+                "--source-dir",
+                source_dir,
+                "--output-types",
+                fmt.as_str(),
+                "--branch",
+                "--ignore-not-existing",
+                "--ignore",
+                "../*",
+                "--ignore",
+                "/*",
+                "--ignore",
+                "xtask/*",
+                "-o",
+                output_folder,
+            )
+            .dir(project_root)
+            .run()?;
+            Ok(())
+        })?;
+
+        let profraw = project_root.join("**").join("*.profraw");
+        with_spinner("cleaning up", || clean_files(&profraw.to_string_lossy()))?;
+
+        Ok(())
+    }
+}
+
+fn run_llvm_cov(t: &Coverage, project_root: &Path, coverage_dir: &Path) -> AnyResult<()> {
+    require_cargo_subcommand("llvm-cov", "cargo-llvm-cov")?;
+    let mut args: Vec<OsString> = vec!["llvm-cov".into(), "--all-features".into()];
+    args.extend(verbosity().cargo_args().iter().map(OsString::from));
+    if let Some(jobs) = t.jobs {
+        args.extend(["--jobs".into(), jobs.to_string().into()]);
+    }
+    if let Some(package) = &t.package {
+        args.extend(["-p".into(), package.into()]);
+    }
+    match t.fmt {
+        // writes `coverage/html/index.html`, like grcov
+        CoverageFormat::Html => {
+            args.extend(["--html".into(), "--output-dir".into(), coverage_dir.into()]);
+        }
+        CoverageFormat::Lcov => args.extend([
+            "--lcov".into(),
+            "--output-path".into(),
+            coverage_dir.join("lcov").into(),
+        ]),
+        CoverageFormat::Cobertura => args.extend([
+            "--cobertura".into(),
+            "--output-path".into(),
+            coverage_dir.join("cobertura.xml").into(),
+        ]),
+        CoverageFormat::Profraw => args.push("--no-report".into()),
+        CoverageFormat::Covdir => bail!("the covdir format is only supported by grcov"),
+    }
+    // like grcov runs, share the target directory of the current project
+    let c = cmd("cargo", &args)
+        .dir(project_root)
+        .env("CARGO_TARGET_DIR", target_dir()?);
+    let c = if t.extra_rustflags.is_empty() {
+        c
+    } else {
+        c.env(
+            "RUSTFLAGS",
+            merged_flags("RUSTFLAGS", &t.extra_rustflags.join(" ")),
+        )
+    };
+    c.run()?;
+    Ok(())
+}
+
+///
+/// Run coverage
+///
+/// # Errors
+/// Fails if any command fails
+///
+pub fn coverage<F>(fmt: F) -> AnyResult<()>
+where
+    F: TryInto<CoverageFormat>,
+    F::Error: Into<anyhow::Error>,
+{
+    CoverageBuilder::default()
+        .fmt(fmt.try_into().map_err(Into::into)?)
+        .run()
+}
+
+/// Build a powerset test
+#[derive(Builder)]
+#[builder(setter(into))]
+pub struct Powerset {
+    /// powerset depth
+    #[builder(default = "2")]
+    pub depth: i32,
+
+    /// dont run with no feature at all
+    #[builder(default = "false")]
+    pub exclude_no_default_features: bool,
+
+    /// workspace crates to leave out
+    /// default: xtask
+    #[builder(default = "vec![\"xtask\".to_string()]")]
+    pub exclude: Vec<String>,
+
+    /// explicit feature combinations to run instead of the powerset, each on top
+    /// of the default features, e.g. `[["serde"], ["serde", "async"]]`.
+    /// `depth` and `exclude_no_default_features` only apply to the powerset.
+    /// default: none, run the powerset
+    #[builder(default)]
+    pub feature_sets: Vec<Vec<String>>,
+
+    /// only build the powerset from these features (`--include-features`)
+    /// default: all features
+    #[builder(default)]
+    pub include_features: Vec<String>,
+
+    /// leave these features out of the powerset (`--exclude-features`), e.g. always-on ones
+    /// default: none
+    #[builder(default)]
+    pub exclude_features: Vec<String>,
+
+    /// comma separated features that are only ever enabled together, one
+    /// `--group-features` per entry, e.g. `"tokio,tokio-util"`. A group counts
+    /// as a single feature towards `depth`.
+    /// default: none
+    #[builder(default)]
+    pub group_features: Vec<String>,
+
+    /// run clippy across the powerset
+    /// default: on
+    #[builder(default = "true")]
+    pub run_clippy: bool,
+
+    /// run tests across the powerset
+    /// default: on
+    #[builder(default = "true")]
+    pub run_tests: bool,
+
+    /// run doc tests across the powerset
+    /// default: on
+    #[builder(default = "true")]
+    pub run_doc_tests: bool,
+}
+
+impl PowersetBuilder {
+    /// Builds and runs a powerset test
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if run failed
+    pub fn run(&self) -> AnyResult<()> {
+        for args in self.build()?.commands() {
+            cmd("cargo", &args).run()?;
+        }
+        Ok(())
+    }
+}
+
+impl Powerset {
+    /// Arguments of every `cargo hack` run, in order
+    fn commands(&self) -> Vec<Vec<String>> {
+        let depth = format!("{}", self.depth);
+        let joined_sets: Vec<String> = self.feature_sets.iter().map(|set| set.join(",")).collect();
+        let include = self.include_features.join(",");
+        let exclude = self.exclude_features.join(",");
+        let variants: Vec<Vec<&str>> = if joined_sets.is_empty() {
+            let mut common = hack_common_args(&self.exclude, self.exclude_no_default_features);
+            common.extend(["--feature-powerset", "--depth", &depth]);
+            if !include.is_empty() {
+                common.extend(["--include-features", &include]);
+            }
+            if !exclude.is_empty() {
+                common.extend(["--exclude-features", &exclude]);
+            }
+            for group in &self.group_features {
+                common.extend(["--group-features", group]);
+            }
+            vec![common]
+        } else {
+            joined_sets
+                .iter()
+                .map(|features| {
+                    let mut common = workspace_args(&self.exclude);
+                    if !features.is_empty() {
+                        common.extend(["--features", features]);
+                    }
+                    common
+                })
+                .collect()
+        };
+        let mut commands: Vec<Vec<&str>> = vec![];
+        for common in &variants {
+            if self.run_clippy {
+                commands.push(
+                    [
+                        &["hack", "clippy"],
+                        common.as_slice(),
+                        &["--", "-D", "warnings"],
+                    ]
+                    .concat(),
+                );
+            }
+            if self.run_tests {
+                commands.push([&["hack"], common.as_slice(), &["test"]].concat());
+            }
+            if self.run_doc_tests {
+                commands.push([&["hack", "test"], common.as_slice(), &["--doc"]].concat());
+            }
+        }
+        commands
+            .into_iter()
+            .map(|args| args.into_iter().map(ToString::to_string).collect())
+            .collect()
+    }
+}
+
+fn workspace_args(exclude: &[String]) -> Vec<&str> {
+    let mut args = vec!["--workspace"];
+    for krate in exclude {
+        args.extend(["--exclude", krate]);
+    }
+    args
+}
+
+fn hack_common_args(exclude: &[String], exclude_no_default_features: bool) -> Vec<&str> {
+    let mut common = workspace_args(exclude);
+    if exclude_no_default_features {
+        common.push("--exclude-no-default-features");
+    }
+    common
+}
+
+///
+/// Perform a CI build with powerset of features
+///
+/// # Errors
+/// Errors if one of the commands failed
+///
+pub fn powerset() -> AnyResult<()> {
+    PowersetBuilder::default().run()
+}
+
+/// Build an each-feature check
+#[derive(Builder)]
+#[builder(setter(into))]
+pub struct EachFeature {
+    /// dont run with no feature at all
+    #[builder(default = "false")]
+    pub exclude_no_default_features: bool,
+
+    /// workspace crates to leave out
+    /// default: xtask
+    #[builder(default = "vec![\"xtask\".to_string()]")]
+    pub exclude: Vec<String>,
+}
+
+impl EachFeatureBuilder {
+    /// Builds and runs an each-feature check
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if run failed
+    pub fn run(&self) -> AnyResult<()> {
+        let t = self.build()?;
+        let common = hack_common_args(&t.exclude, t.exclude_no_default_features);
+        cmd(
+            "cargo",
+            &[&["hack", "check"], common.as_slice(), &["--each-feature"]].concat(),
+        )
+        .run()?;
+        Ok(())
+    }
+}
+
+///
+/// Check that every feature builds on its own. Much cheaper than `powerset`.
+///
+/// # Errors
+/// Errors if the command failed
+///
+pub fn each_feature() -> AnyResult<()> {
+    EachFeatureBuilder::default().run()
+}
+
+///
+/// Run `cargo check` only for the workspace packages that own files changed
+/// since the git revision `base` (e.g. `HEAD` or `origin/main`). Much faster
+/// than checking everything in a large workspace.
+///
+/// # Errors
+/// Errors if git or cargo fail
+///
+pub fn check_changed(base: &str) -> AnyResult<()> {
+    let toplevel = PathBuf::from(cmd!("git", "rev-parse", "--show-toplevel").read()?);
+    // both list paths relative to the repository root
+    let diff = cmd!("git", "diff", "--name-only", base)
+        .dir(&toplevel)
+        .read()?;
+    let untracked = cmd!(
+        "git",
+        "ls-files",
+        "--others",
+        "--exclude-standard",
+        "--full-name"
+    )
+    .dir(&toplevel)
+    .read()?;
+    let packages = workspace_members()?;
+
+    let mut changed = BTreeSet::new();
+    for file in diff.lines().chain(untracked.lines()) {
+        let file = toplevel.join(file);
+        let owner = packages
+            .iter()
+            .filter(|p| file.starts_with(p.dir()))
+            .max_by_key(|p| p.dir().components().count());
+        if let Some(p) = owner {
+            changed.insert(p.name.as_str());
+        }
+    }
+
+    if changed.is_empty() {
+        status(&format!("no workspace packages changed since {base}"), "1");
+        return Ok(());
+    }
+    let mut args = vec!["check"];
+    for name in changed {
+        args.extend(["-p", name]);
+    }
+    cmd("cargo", &args).run()?;
+    Ok(())
+}
+
+///
+/// Run `cargo <command> <args>` in the directory of every workspace member,
+/// continuing past failures
+///
+/// # Errors
+/// Fails if the command failed for any member, listing those members
+///
+pub fn for_each_member(command: &str, args: &[&str]) -> AnyResult<()> {
+    let mut failed = Vec::new();
+    for member in workspace_members()? {
+        let line = [command]
+            .iter()
+            .chain(args)
+            .copied()
+            .collect::<Vec<_>>()
+            .join(" ");
+        status(&format!("=== {}: cargo {line} ===", member.name), "1");
+        let status = cmd("cargo", [command].iter().chain(args))
+            .dir(member.dir())
+            .unchecked()
+            .run()?
+            .status;
+        if !status.success() {
+            failed.push(member.name);
+        }
+    }
+    if !failed.is_empty() {
+        bail!("cargo {command} failed for: {}", failed.join(", "));
+    }
+    Ok(())
+}
+
+/// Build a `cargo check --no-default-features` run over the workspace
+#[derive(Builder)]
+#[builder(setter(into))]
+pub struct CheckNoDefaultFeatures {
+    /// workspace crates to leave out
+    /// default: xtask
+    #[builder(default = "vec![\"xtask\".to_string()]")]
+    pub exclude: Vec<String>,
+}
+
+impl CheckNoDefaultFeaturesBuilder {
+    /// Builds and runs the check
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if run failed
+    pub fn run(&self) -> AnyResult<()> {
+        let t = self.build()?;
+        cmd(
+            "cargo",
+            [
+                &["check", "--no-default-features"],
+                workspace_args(&t.exclude).as_slice(),
+            ]
+            .concat(),
+        )
+        .run()?;
+        Ok(())
+    }
+}
+
+///
+/// Check that the workspace builds with `--no-default-features`, catching
+/// code that silently relies on a default feature
+///
+/// # Errors
+/// Errors if the command failed
+///
+pub fn check_no_default_features() -> AnyResult<()> {
+    CheckNoDefaultFeaturesBuilder::default().run()
+}
+
+///
+/// Check that the workspace builds against the minimal dependency versions
+/// allowed by its version bounds. Requires a nightly toolchain.
+///
+/// This rewrites `Cargo.lock` with `cargo -Z minimal-versions update`. The
+/// original lockfile is restored afterwards (or removed if there was none),
+/// also when the check fails.
+///
+/// # Errors
+/// Errors if nightly is not installed or one of the commands failed
+///
+pub fn minimal_versions() -> AnyResult<()> {
+    if !nightly_available() {
+        bail!(
+            "minimal-versions requires a nightly toolchain, run `rustup toolchain install nightly`"
+        );
+    }
+    let lockfile = get_workspace_root()?.join("Cargo.lock");
+    // the update rewrites the lockfile, so a dry run stops before it
+    if planned(PlannedOp::RunCommand(
+        "cargo +nightly -Z minimal-versions update".to_string(),
+    )) {
+        return Ok(());
+    }
+    let original = fs::read(&lockfile).ok();
+
+    let res = cmd!("cargo", "+nightly", "-Z", "minimal-versions", "update")
+        .run()
+        .and_then(|_| cmd!("cargo", "check").run());
+
+    match original {
+        Some(contents) => write_atomic(&lockfile, &contents)?,
+        None => remove_file(&lockfile)?,
+    }
+    res?;
+    Ok(())
+}
+
+/// Build a Miri test run
+#[derive(Builder)]
+#[builder(setter(into))]
+pub struct Miri {
+    /// flags passed through `MIRIFLAGS`, e.g. `-Zmiri-strict-provenance`
+    #[builder(default)]
+    pub miriflags: Option<String>,
+
+    /// install the miri component if it is missing
+    /// default: off
+    #[builder(default = "false")]
+    pub install: bool,
+}
+
+impl MiriBuilder {
+    /// Builds and runs tests under Miri
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if miri is missing or run failed
+    pub fn run(&self) -> AnyResult<()> {
+        let t = self.build()?;
+        if !succeeds(&cmd!("cargo", "+nightly", "miri", "--version")) {
+            if !t.install {
+                bail!("miri is not installed, run `rustup +nightly component add miri`");
+            }
+            cmd!("rustup", "+nightly", "component", "add", "miri").run()?;
+        }
+
+        let mut miri = cmd!("cargo", "+nightly", "miri", "test");
+        if let Some(flags) = &t.miriflags {
+            miri = miri.env("MIRIFLAGS", merged_flags("MIRIFLAGS", flags));
+        }
+        miri.run()?;
+        Ok(())
+    }
+}
+
+///
+/// Run tests under Miri to catch undefined behavior in unsafe code
+///
+/// # Errors
+/// Errors if miri is missing or the command failed
+///
+pub fn miri() -> AnyResult<()> {
+    MiriBuilder::default().run()
+}
+
+///
+/// List the cargo-fuzz targets declared in `fuzz/Cargo.toml`
+///
+/// # Errors
+/// Errors if the fuzz manifest cannot be read or parsed
+///
+pub fn fuzz_targets() -> AnyResult<Vec<String>> {
+    let manifest = nearest_cargo_dir()?.join("fuzz").join("Cargo.toml");
+    let doc: DocumentMut = fs::read_to_string(&manifest)
+        .with_context(|| format!("cannot read {}", manifest.display()))?
+        .parse()?;
+    Ok(doc
+        .get("bin")
+        .and_then(Item::as_array_of_tables)
+        .map(|bins| {
+            bins.iter()
+                .filter_map(|bin| bin.get("name").and_then(Item::as_str))
+                .map(String::from)
+                .collect()
+        })
+        .unwrap_or_default())
+}
+
+///
+/// Run a cargo-fuzz target for `duration_secs` seconds. With an empty
+/// `target`, list the available fuzz targets instead.
+///
+/// # Errors
+/// Errors if the fuzzer found a crash or the command failed
+///
+pub fn fuzz(target: &str, duration_secs: u64) -> AnyResult<()> {
+    if target.is_empty() {
+        println!("fuzz targets:");
+        for target in fuzz_targets()? {
+            println!("  {target}");
+        }
+        return Ok(());
+    }
+    let max_total_time = format!("-max_total_time={duration_secs}");
+    cmd!(
+        "cargo",
+        "+nightly",
+        "fuzz",
+        "run",
+        target,
+        "--",
+        max_total_time
+    )
+    .run()
+    .with_context(|| {
+        format!("fuzz target `{target}` failed, crash inputs are in fuzz/artifacts/{target}")
+    })?;
+    Ok(())
+}
+
+///
+/// Format the workspace's TOML files with `taplo`, or only check
+/// their formatting when `check` is set
+///
+/// # Errors
+/// Errors if files are not formatted (in check mode) or the command failed
+///
+pub fn fmt_toml(check: bool) -> AnyResult<()> {
+    let mut args = vec!["fmt"];
+    if check {
+        args.push("--check");
+    }
+    cmd("taplo", &args).dir(get_workspace_root()?).run()?;
+    Ok(())
+}
+
+/// Build a `typos` spellcheck of the workspace
+#[derive(Builder)]
+#[builder(setter(into))]
+pub struct Spellcheck {
+    /// fix the typos in place (`-w`) instead of failing on them
+    /// default: off
+    #[builder(default = "false")]
+    pub write: bool,
+}
+
+impl SpellcheckBuilder {
+    /// Builds and runs `typos` from the workspace root
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if typos is missing, or, when not
+    /// writing, a typo was found
+    pub fn run(&self) -> AnyResult<()> {
+        let t = self.build()?;
+        if !succeeds(&cmd!("typos", "--version")) {
+            bail!("typos is not installed, run `cargo install typos-cli`");
+        }
+        let mut args = vec![];
+        if t.write {
+            args.push("-w");
+        }
+        let status = cmd("typos", &args)
+            .dir(get_workspace_root()?)
+            .unchecked()
+            .run()?
+            .status;
+        // typos exits with 2 when it finds typos, other failures are errors such as a bad config
+        match status.code() {
+            Some(0) => {}
+            Some(2) => bail!("typos found spelling mistakes, run with `write` to fix them"),
+            _ => bail!("`typos` failed ({status}), see the output above"),
+        }
+        Ok(())
+    }
+}
+
+///
+/// Fail on spelling mistakes in the workspace, using `typos`
+///
+/// # Errors
+/// Errors if typos is missing or found a typo
+///
+pub fn spellcheck() -> AnyResult<()> {
+    SpellcheckBuilder::default().run()
+}
+
+/// Build a dependency license report
+#[derive(Builder)]
+#[builder(setter(into))]
+pub struct Licenses {
+    /// report format: text, json or tsv
+    /// default: text
+    #[builder(default = "\"text\".to_string()")]
+    pub format: String,
+
+    /// write the report to this file instead of stdout
+    #[builder(default)]
+    pub output: Option<PathBuf>,
+}
+
+impl LicensesBuilder {
+    /// Builds and runs the license report with `cargo license`
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if run failed
+    pub fn run(&self) -> AnyResult<()> {
+        let t = self.build()?;
+        let format_arg = match t.format.as_str() {
+            "text" => None,
+            "json" => Some("--json"),
+            "tsv" => Some("--tsv"),
+            other => bail!("unknown license report format `{other}`, expected text, json or tsv"),
+        };
+        let license = cmd("cargo", ["license"].into_iter().chain(format_arg));
+        match &t.output {
+            Some(path) => {
+                write_atomic(path, license.read()?.as_bytes())?;
+                status(
+                    &format!("license report written to {}", path.display()),
+                    "32",
+                );
+            }
+            None => {
+                license.run()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+///
+/// Print the licenses of all dependencies
+///
+/// # Errors
+/// Errors if the command failed
+///
+pub fn licenses() -> AnyResult<()> {
+    LicensesBuilder::default().run()
+}
+
+/// Tokens rejected by `check_forbidden` when no patterns are given
+pub const DEFAULT_FORBIDDEN: &[&str] = &["dbg!(", "todo!(", "unimplemented!("];
+
+/// Blank out comments and the contents of string and char literals, keeping
+/// line breaks, so that only code is left to search
+fn strip_comments_and_literals(source: &str) -> String {
+    let chars: Vec<char> = source.chars().collect();
+    let mut out = String::with_capacity(source.len());
+    // blanks `chars[from..to]`, keeping newlines so line numbers still match
+    let blank = |out: &mut String, from: usize, to: usize| {
+        out.extend(
+            chars[from..to]
+                .iter()
+                .map(|&c| if c == '\n' { c } else { ' ' }),
+        );
+    };
+    let is_ident = |c: char| c.is_alphanumeric() || c == '_';
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        let after_ident = i > 0 && is_ident(chars[i - 1]);
+        if c == '/' && next == Some('/') {
+            let end = chars[i..]
+                .iter()
+                .position(|&c| c == '\n')
+                .map_or(chars.len(), |n| i + n);
+            blank(&mut out, i, end);
+            i = end;
+        } else if c == '/' && next == Some('*') {
+            let (mut depth, mut end) = (0, i);
+            while end < chars.len() {
+                match (chars[end], chars.get(end + 1)) {
+                    ('/', Some('*')) => (depth, end) = (depth + 1, end + 2),
+                    ('*', Some('/')) => (depth, end) = (depth - 1, end + 2),
+                    _ => end += 1,
+                }
+                if depth == 0 {
+                    break;
+                }
+            }
+            blank(&mut out, i, end);
+            i = end;
+        } else if c == 'r' && !after_ident && matches!(next, Some('"' | '#')) {
+            let hashes = chars[i + 1..].iter().take_while(|&&c| c == '#').count();
+            let open = i + 1 + hashes;
+            if chars.get(open) != Some(&'"') {
+                // a raw identifier such as `r#type`
+                out.push(c);
+                i += 1;
+                continue;
+            }
+            let closing: Vec<char> = std::iter::once('"')
+                .chain(std::iter::repeat_n('#', hashes))
+                .collect();
+            let end = (open + 1..chars.len())
+                .find(|&at| chars[at..].starts_with(&closing))
+                .map_or(chars.len(), |at| at + closing.len());
+            out.extend(&chars[i..=open]);
+            blank(&mut out, open + 1, end);
+            i = end;
+        } else if c == '"' {
+            let mut end = i + 1;
+            while end < chars.len() && chars[end] != '"' {
+                end += if chars[end] == '\\' { 2 } else { 1 };
+            }
+            out.push(c);
+            blank(&mut out, i + 1, end.min(chars.len()));
+            i = end + 1;
+            if end < chars.len() {
+                out.push('"');
+            }
+        } else if c == '\'' && (next == Some('\\') || chars.get(i + 2) == Some(&'\'')) {
+            // a char literal rather than a lifetime
+            let mut end = i + 2;
+            while end < chars.len() && chars[end] != '\'' {
+                end += 1;
+            }
+            blank(&mut out, i, (end + 1).min(chars.len()));
+            i = end + 1;
+        } else {
+            out.push(c);
+            i += 1;
+        }
+    }
+    out
+}
+
+///
+/// Look for leftover tokens such as `dbg!(` in the sources under `src/`,
+/// honoring `.gitignore`. Comments and string literals are not searched.
+/// An empty `patterns` uses [`DEFAULT_FORBIDDEN`].
+///
+/// # Errors
+/// Errors listing every `file:line` hit, or if the sources cannot be read
+///
+pub fn check_forbidden(patterns: &[&str]) -> AnyResult<()> {
+    let patterns = if patterns.is_empty() {
+        DEFAULT_FORBIDDEN
+    } else {
+        patterns
+    };
+    let root = nearest_cargo_dir()?;
+    let mut hits = vec![];
+    for file in source_files(&root)? {
+        let display = relative_path(&root, &file).unwrap_or_else(|| file.clone());
+        let code = strip_comments_and_literals(&fs::read_to_string(&file)?);
+        for (idx, line) in code.lines().enumerate() {
+            for pattern in patterns.iter().filter(|p| line.contains(*p)) {
+                hits.push(format!("{}:{}: {pattern}", display.display(), idx + 1));
+            }
+        }
+    }
+    if !hits.is_empty() {
+        bail!("found forbidden tokens:\n{}", hits.join("\n"));
+    }
+    Ok(())
+}
+
+/// Doc-test attributes that keep an example from being run
+const SKIPPED_DOCTEST_ATTRS: &[&str] = &["ignore", "no_run"];
+
+/// Attributes of a doc code fence opening, e.g. `rust,no_run` in ```` ```rust,no_run ````
+fn doc_fence_attrs(doc: &str) -> Option<&str> {
+    let doc = doc.trim_start();
+    ["```", "~~~"]
+        .iter()
+        .find_map(|fence| doc.strip_prefix(fence))
+        .map(|attrs| attrs.trim_start_matches(['`', '~']).trim())
+}
+
+///
+/// List doc examples marked `ignore` or `no_run` in `src` files, as `file:line: attribute`,
+/// so each one can be justified. Files excluded by `.gitignore` are skipped.
+///
+/// # Errors
+/// Fails if `deny` is set and any such example is found, or if a source file cannot be read
+///
+pub fn check_doc_fences(deny: bool) -> AnyResult<()> {
+    let root = nearest_cargo_dir()?;
+    let mut hits = vec![];
+    for file in source_files(&root)? {
+        let display = relative_path(&root, &file).unwrap_or_else(|| file.clone());
+        let mut in_fence = false;
+        for (idx, line) in fs::read_to_string(&file)?.lines().enumerate() {
+            let trimmed = line.trim_start();
+            let Some(doc) = ["///", "//!"]
+                .iter()
+                .find_map(|prefix| trimmed.strip_prefix(prefix))
+            else {
+                // a fence never spans past the end of a doc comment
+                in_fence = false;
+                continue;
+            };
+            let Some(attrs) = doc_fence_attrs(doc) else {
+                continue;
+            };
+            if in_fence {
+                in_fence = false;
+                continue;
+            }
+            in_fence = true;
+            for attr in attrs
+                .split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|a| SKIPPED_DOCTEST_ATTRS.contains(a))
+            {
+                hits.push(format!("{}:{}: {attr}", display.display(), idx + 1));
+            }
+        }
+    }
+    if hits.is_empty() {
+        status("no ignored or no_run doc examples", "32");
+        return Ok(());
+    }
+    println!("{}", hits.join("\n"));
+    if deny {
+        bail!("found {} ignored or no_run doc examples", hits.len());
+    }
+    Ok(())
+}
+
+/// Fails with an install hint when `cargo <subcommand>` is not available
+fn require_cargo_subcommand(subcommand: &str, krate: &str) -> AnyResult<()> {
+    if !succeeds(&cmd!("cargo", subcommand, "--version")) {
+        bail!("{krate} is not installed, run `cargo install {krate}`");
+    }
+    Ok(())
+}
+
+/// Build a cargo-audit security scan
+#[derive(Builder)]
+#[builder(setter(into))]
+pub struct Audit {
+    /// fail on warnings such as unmaintained or yanked crates (`--deny warnings`)
+    /// default: off
+    #[builder(default = "false")]
+    pub deny_warnings: bool,
+
+    /// advisory IDs to ignore (`--ignore`), e.g. `RUSTSEC-2020-0071`
+    /// default: none
+    #[builder(default)]
+    pub ignore: Vec<String>,
+}
+
+impl AuditBuilder {
+    /// Builds and runs `cargo audit`
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if cargo-audit is missing or finds vulnerabilities
+    pub fn run(&self) -> AnyResult<()> {
+        let t = self.build()?;
+        require_cargo_subcommand("audit", "cargo-audit")?;
+        let mut args = vec!["audit"];
+        if t.deny_warnings {
+            args.extend(["--deny", "warnings"]);
+        }
+        for id in &t.ignore {
+            args.extend(["--ignore", id]);
+        }
+        cmd("cargo", &args).run()?;
+        Ok(())
+    }
+}
+
+///
+/// Scan `Cargo.lock` for crates with security advisories
+///
+/// # Errors
+/// Errors if cargo-audit is missing or found vulnerabilities
+///
+pub fn audit() -> AnyResult<()> {
+    AuditBuilder::default().run()
+}
+
+/// Checks `cargo deny check` can run
+pub const DENY_CHECKS: &[&str] = &["advisories", "bans", "licenses", "sources"];
+
+/// Build a cargo-deny run
+#[derive(Builder)]
+#[builder(setter(into))]
+pub struct Deny {
+    /// checks to run, out of `DENY_CHECKS`
+    /// default: all
+    #[builder(default)]
+    pub checks: Vec<String>,
+}
+
+impl DenyBuilder {
+    /// Builds and runs `cargo deny check`
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if cargo-deny or its config is missing, or a check fails
+    pub fn run(&self) -> AnyResult<()> {
+        let t = self.build()?;
+        if let Some(check) = t.checks.iter().find(|c| !DENY_CHECKS.contains(&c.as_str())) {
+            bail!(
+                "unknown cargo-deny check `{check}`, expected one of: {}",
+                DENY_CHECKS.join(", ")
+            );
+        }
+        require_cargo_subcommand("deny", "cargo-deny")?;
+        let root = get_workspace_root()?;
+        if !["deny.toml", ".deny.toml", ".cargo/deny.toml"]
+            .iter()
+            .any(|config| root.join(config).exists())
+        {
+            bail!(
+                "no deny.toml in {}, create one with `cargo deny init`",
+                root.display()
+            );
+        }
+        let args = [
+            &["deny", "check"],
+            t.checks
+                .iter()
+                .map(String::as_str)
+                .collect::<Vec<_>>()
+                .as_slice(),
+        ]
+        .concat();
+        cmd("cargo", &args).dir(root).run()?;
+        Ok(())
+    }
+}
+
+///
+/// Check licenses, bans, advisories and sources with cargo-deny
+///
+/// # Errors
+/// Errors if cargo-deny or its config is missing, or a check fails
+///
+pub fn deny() -> AnyResult<()> {
+    DenyBuilder::default().run()
+}
+
+/// Build a cargo-outdated report
+#[derive(Builder)]
+#[builder(setter(into))]
+pub struct Outdated {
+    /// report on every workspace member (`--workspace`)
+    /// default: off
+    #[builder(default = "false")]
+    pub workspace: bool,
+
+    /// fail when a dependency is outdated (`--exit-code 1`)
+    /// default: off
+    #[builder(default = "false")]
+    pub exit_on_outdated: bool,
+}
+
+impl OutdatedBuilder {
+    /// Builds and runs `cargo outdated`
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if cargo-outdated is missing, or with
+    /// `exit_on_outdated` when a dependency is outdated
+    pub fn run(&self) -> AnyResult<()> {
+        let t = self.build()?;
+        require_cargo_subcommand("outdated", "cargo-outdated")?;
+        let mut args = vec!["outdated"];
+        if t.workspace {
+            args.push("--workspace");
+        }
+        if t.exit_on_outdated {
+            args.extend(["--exit-code", "1"]);
+        }
+        let status = cmd("cargo", &args).unchecked().run()?.status;
+        if !status.success() {
+            if t.exit_on_outdated && status.code() == Some(1) {
+                bail!("some dependencies are outdated");
+            }
+            bail!("cargo outdated failed ({status})");
+        }
+        Ok(())
+    }
+}
+
+///
+/// Report dependencies that have newer versions
+///
+/// # Errors
+/// Errors if cargo-outdated is missing or failed
+///
+pub fn outdated() -> AnyResult<()> {
+    OutdatedBuilder::default().run()
+}
+
+/// Build a cargo-geiger unsafe audit
+#[derive(Builder)]
+#[builder(setter(into))]
+pub struct Geiger {
+    /// fail when the workspace crates use more unsafe than this
+    /// default: no limit
+    #[builder(default)]
+    pub max_unsafe: Option<u64>,
+}
+
+fn geiger_unsafe_count(used: &Value) -> u64 {
+    ["functions", "exprs", "item_impls", "item_traits", "methods"]
+        .iter()
+        .filter_map(|kind| used.get(kind))
+        .filter_map(|count| count.get("unsafe_").or_else(|| count.get("unsafe")))
+        .filter_map(Value::as_u64)
+        .sum()
+}
+
+impl GeigerBuilder {
+    /// Builds and runs the unsafe audit
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the limit is exceeded or run failed
+    pub fn run(&self) -> AnyResult<()> {
+        let t = self.build()?;
+        let members: Vec<String> = workspace_members()?.into_iter().map(|p| p.name).collect();
+        let report: Value =
+            serde_json::from_str(&cmd!("cargo", "geiger", "--output-format", "Json").read()?)?;
+
+        let (mut own, mut deps) = (0, 0);
+        for entry in report
+            .get("packages")
+            .and_then(Value::as_array)
+            .context("unexpected cargo-geiger output")?
+        {
+            let name = entry
+                .pointer("/package/id/name")
+                .and_then(Value::as_str)
+                .unwrap_or_default();
+            let count = entry
+                .pointer("/unsafety/used")
+                .map_or(0, geiger_unsafe_count);
+            if count > 0 {
+                println!("{name}: {count}");
+            }
+            if members.iter().any(|m| m == name) {
+                own += count;
+            } else {
+                deps += count;
+            }
+        }
+        println!("unsafe usages: {own} in workspace crates, {deps} in dependencies");
+
+        if let Some(max) = t.max_unsafe {
+            if own > max {
+                bail!("workspace crates use unsafe {own} times, more than the allowed {max}");
+            }
+        }
+        Ok(())
+    }
+}
+
+///
+/// Report unsafe usage in the crate and its dependencies with cargo-geiger
+///
+/// # Errors
+/// Errors if the command failed
+///
+pub fn geiger() -> AnyResult<()> {
+    GeigerBuilder::default().run()
+}
+
+///
+/// Report direct dependencies that workspace members use at different
+/// versions. With `deny`, fail when there are any.
+///
+/// # Errors
+/// Errors if cargo metadata fails, or with `deny` when versions differ
+///
+pub fn check_dep_consistency(deny: bool) -> AnyResult<()> {
+    let metadata = cargo_metadata()?;
+    let packages: HashMap<&str, &PackageInfo> = metadata
+        .packages
+        .iter()
+        .map(|p| (p.id.as_str(), p))
+        .collect();
+    let nodes = &metadata
+        .resolve
+        .as_ref()
+        .context("cargo metadata has no resolve graph")?
+        .nodes;
+    let is_member = |id: &str| metadata.workspace_members.iter().any(|m| m == id);
+
+    // dependency name -> version -> members using it
+    let mut versions: BTreeMap<&str, BTreeMap<&str, BTreeSet<&str>>> = BTreeMap::new();
+    for node in nodes.iter().filter(|n| is_member(&n.id)) {
+        let Some(member) = packages.get(node.id.as_str()) else {
+            continue;
+        };
+        for dep in node.deps.iter().filter(|d| !is_member(&d.pkg)) {
+            if let Some(package) = packages.get(dep.pkg.as_str()) {
+                versions
+                    .entry(&package.name)
+                    .or_default()
+                    .entry(&package.version)
+                    .or_default()
+                    .insert(&member.name);
+            }
+        }
+    }
+
+    let mismatched: Vec<_> = versions.iter().filter(|(_, v)| v.len() > 1).collect();
+    if mismatched.is_empty() {
+        status("workspace members agree on all dependency versions", "32");
+        return Ok(());
+    }
+    for (name, by_version) in &mismatched {
+        let uses: Vec<String> = by_version
+            .iter()
+            .map(|(version, users)| {
+                format!(
+                    "{version} ({})",
+                    users.iter().copied().collect::<Vec<_>>().join(", ")
+                )
+            })
+            .collect();
+        println!("{name}: {}", uses.join(", "));
+    }
+    if deny {
+        bail!(
+            "{} dependencies are used at different versions",
+            mismatched.len()
+        );
+    }
+    Ok(())
+}
+
+/// `cargo tree` of the workspace with depth prefixes, local paths made relative to the root
+fn deps_tree() -> AnyResult<String> {
+    let root = get_workspace_root()?;
+    let tree = cmd!(
+        "cargo",
+        "tree",
+        "--workspace",
+        "--prefix",
+        "depth",
+        "--format",
+        "{p}"
+    )
+    .dir(&root)
+    .read()?;
+    let root = root.to_string_lossy();
+    Ok(tree.lines().fold(String::new(), |mut out, line| {
+        out.push_str(&line.replace(root.as_ref(), "."));
+        out.push('\n');
+        out
+    }))
+}
+
+///
+/// Write the current `cargo tree` of the workspace to `snapshot_file`, to be
+/// reviewed and later compared with `deps_check`
+///
+/// # Errors
+/// Errors if `cargo tree` fails or the file cannot be written
+///
+pub fn deps_snapshot(snapshot_file: &str) -> AnyResult<()> {
+    let tree = deps_tree()?;
+    write_atomic(snapshot_file, tree.as_bytes())?;
+    println!(
+        "Dependency snapshot: {} lines written to {snapshot_file}",
+        tree.lines().count()
+    );
+    Ok(())
+}
+
+///
+/// Compare the current `cargo tree` of the workspace with the one in `snapshot_file`
+/// and print the lines that were removed (`-`) or added (`+`)
+///
+/// # Errors
+/// Fails if the dependency tree differs from the snapshot, or if any command fails
+///
+pub fn deps_check(snapshot_file: &str) -> AnyResult<()> {
+    let snapshot = fs::read_to_string(snapshot_file)
+        .with_context(|| format!("cannot read dependency snapshot {snapshot_file}"))?;
+    let current = deps_tree()?;
+
+    // line -> (count in snapshot) - (count now)
+    let mut balance: BTreeMap<&str, i64> = BTreeMap::new();
+    for line in snapshot.lines() {
+        *balance.entry(line).or_default() += 1;
+    }
+    for line in current.lines() {
+        *balance.entry(line).or_default() -= 1;
+    }
+    let mut changed = 0;
+    for (line, count) in balance.iter().filter(|(_, count)| **count != 0) {
+        let sign = if *count > 0 { "-" } else { "+" };
+        for _ in 0..count.abs() {
+            println!("{sign} {line}");
+            changed += 1;
+        }
+    }
+    if changed > 0 {
+        bail!("dependency tree differs from {snapshot_file} ({changed} lines changed)");
+    }
+    status(&format!("dependency tree matches {snapshot_file}"), "32");
+    Ok(())
+}
+
+/// Semver component to bump in a release
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bump {
+    /// `1.2.3` -> `2.0.0`
+    Major,
+    /// `1.2.3` -> `1.3.0`
+    Minor,
+    /// `1.2.3` -> `1.2.4`
+    Patch,
+}
+
+impl FromStr for Bump {
+    type Err = XtaskError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "major" => Ok(Self::Major),
+            "minor" => Ok(Self::Minor),
+            "patch" => Ok(Self::Patch),
+            _ => Err(XtaskError::InvalidArgument(format!(
+                "unknown bump `{s}`, expected major, minor or patch"
+            ))),
+        }
+    }
+}
+
+/// Bump a `major.minor.patch` version, dropping any pre-release or build suffix
+fn bump_version(version: &str, bump: Bump) -> AnyResult<String> {
+    let core = version.split(['-', '+']).next().unwrap_or(version);
+    let parts = core
+        .split('.')
+        .map(str::parse::<u64>)
+        .collect::<Result<Vec<_>, _>>()
+        .ok()
+        .filter(|parts| parts.len() == 3)
+        .with_context(|| format!("`{version}` is not a semver version"))?;
+    let (major, minor, patch) = (parts[0], parts[1], parts[2]);
+    Ok(match bump {
+        Bump::Major => format!("{}.0.0", major + 1),
+        Bump::Minor => format!("{major}.{}.0", minor + 1),
+        Bump::Patch => format!("{major}.{minor}.{}", patch + 1),
+    })
+}
+
+/// Build a release: bump versions in `Cargo.toml`, then optionally commit and tag
+#[derive(Builder)]
+#[builder(setter(into))]
+pub struct Release {
+    /// version component to bump, for each package from its current version
+    /// default: none, `version` must be set
+    #[builder(default)]
+    pub bump: Option<Bump>,
+
+    /// explicit version to release, instead of `bump`
+    /// default: none
+    #[builder(default)]
+    pub version: Option<String>,
+
+    /// workspace packages to release. Members inheriting `workspace.package.version`
+    /// share that version, so bumping one bumps them all.
+    /// default: all members
+    #[builder(default)]
+    pub packages: Vec<String>,
+
+    /// commit the changed manifests and `Cargo.lock`
+    /// default: off
+    #[builder(default = "false")]
+    pub commit: bool,
+
+    /// create an annotated tag on the release commit, `v<version>` or
+    /// `<package>-v<version>` when packages end up at different versions.
+    /// Implies `commit`.
+    /// default: off
+    #[builder(default = "false")]
+    pub tag: bool,
+
+    /// only print the planned changes, also on with `ops::set_dry_run`
+    /// default: off
+    #[builder(default = "false")]
+    pub dry_run: bool,
+}
+
+/// Replaces a string value, keeping its surrounding whitespace and comments
+fn set_str(item: &mut Item, value: &str) {
+    let decor = item.as_value().map(|v| v.decor().clone());
+    *item = toml_edit::value(value);
+    if let (Some(decor), Some(new)) = (decor, item.as_value_mut()) {
+        *new.decor_mut() = decor;
+    }
+}
+
+/// Whether a member manifest inherits `workspace.package.version`
+fn inherits_workspace_version(doc: &DocumentMut) -> bool {
+    doc.get("package")
+        .and_then(|p| p.get("version"))
+        .and_then(Item::as_table_like)
+        .and_then(|v| v.get("workspace"))
+        .and_then(Item::as_bool)
+        == Some(true)
+}
+
+/// Sets `version` in the dependency entries on `released` packages, including
+/// renamed dependencies and the `[target.'cfg(..)'.*dependencies]` tables
+fn set_dependency_versions(
+    table: &mut dyn toml_edit::TableLike,
+    released: &BTreeMap<String, String>,
+) {
+    for kind in ["dependencies", "dev-dependencies", "build-dependencies"] {
+        let Some(deps) = table.get_mut(kind).and_then(Item::as_table_like_mut) else {
+            continue;
+        };
+        for (key, dep) in deps.iter_mut() {
+            let Some(dep) = dep.as_table_like_mut() else {
+                continue;
+            };
+            let name = dep
+                .get("package")
+                .and_then(Item::as_str)
+                .unwrap_or(key.get());
+            let Some(new_version) = released.get(name) else {
+                continue;
+            };
+            if let Some(version) = dep.get_mut("version") {
+                set_str(version, new_version);
+            }
+        }
+    }
+    if let Some(targets) = table.get_mut("target").and_then(Item::as_table_like_mut) {
+        for (_, target) in targets.iter_mut() {
+            if let Some(target) = target.as_table_like_mut() {
+                set_dependency_versions(target, released);
+            }
+        }
+    }
+}
+
+impl ReleaseBuilder {
+    /// Builds and runs the release
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if a version cannot be bumped, a manifest
+    /// cannot be edited, or a git command fails
+    pub fn run(&self) -> AnyResult<()> {
+        let t = self.build()?;
+        if t.bump.is_some() == t.version.is_some() {
+            bail!("set exactly one of `bump` and `version`");
+        }
+        let root = get_workspace_root()?;
+        let root_manifest = root.join("Cargo.toml");
+        let mut root_doc: DocumentMut = fs::read_to_string(&root_manifest)?.parse()?;
+        let members = workspace_members()?;
+        if let Some(unknown) = t
+            .packages
+            .iter()
+            .find(|p| !members.iter().any(|m| m.name == **p))
+        {
+            bail!("`{unknown}` is not a workspace member");
+        }
+        let new_version = |current: &str| match (&t.version, t.bump) {
+            (Some(version), _) => Ok(version.clone()),
+            (None, Some(bump)) => bump_version(current, bump),
+            (None, None) => unreachable!("checked above"),
+        };
+
+        // package name -> new version, and every member manifest with whether
+        // its own `package.version` is bumped
+        let mut docs = vec![];
+        for member in &members {
+            let doc: DocumentMut = fs::read_to_string(&member.manifest_path)?.parse()?;
+            let inherited = inherits_workspace_version(&doc);
+            docs.push((member, doc, inherited));
+        }
+        let selected = |name: &String| t.packages.is_empty() || t.packages.contains(name);
+        // bumping the workspace version releases every member that inherits it
+        let bumps_workspace = docs
+            .iter()
+            .any(|(member, _, inherited)| *inherited && selected(&member.name));
+
+        let mut released = BTreeMap::new();
+        let mut manifests = vec![];
+        let mut workspace_version = None;
+        for (member, doc, inherited) in docs {
+            let name = &member.name;
+            let manifest = member.manifest_path.clone();
+            if !selected(name) && !(inherited && bumps_workspace) {
+                // may still depend on released packages
+                manifests.push((manifest, doc, false));
+                continue;
+            }
+            let version = doc.get("package").and_then(|p| p.get("version"));
+            let (current, next) = if inherited {
+                let current = root_doc
+                    .get("workspace")
+                    .and_then(|w| w.get("package"))
+                    .and_then(|p| p.get("version"))
+                    .and_then(Item::as_str)
+                    .context("`version.workspace = true` without [workspace.package] version")?
+                    .to_string();
+                let next = workspace_version
+                    .get_or_insert(new_version(&current)?)
+                    .clone();
+                (current, next)
+            } else {
+                let current = version
+                    .and_then(Item::as_str)
+                    .with_context(|| format!("{} has no package version", manifest.display()))?
+                    .to_string();
+                let next = new_version(&current)?;
+                (current, next)
+            };
+            println!("{name}: {current} -> {next}");
+            released.insert(name.clone(), next);
+            manifests.push((manifest, doc, !inherited));
+        }
+
+        let mut changed = vec![];
+        for (manifest, mut doc, own_version) in manifests {
+            if own_version {
+                let name = doc["package"]["name"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string();
+                set_str(&mut doc["package"]["version"], &released[&name]);
+            }
+            set_dependency_versions(doc.as_table_mut(), &released);
+            if manifest == root_manifest {
+                root_doc = doc;
+            } else {
+                changed.push((manifest, doc.to_string()));
+            }
+        }
+        if let Some(version) = &workspace_version {
+            set_str(&mut root_doc["workspace"]["package"]["version"], version);
+        }
+        if let Some(workspace) = root_doc.get_mut("workspace").and_then(Item::as_table_mut) {
+            set_dependency_versions(workspace, &released);
+        }
+        changed.push((root_manifest, root_doc.to_string()));
+        let mut unchanged = vec![];
+        for (manifest, contents) in &changed {
+            if fs::read_to_string(manifest)? == *contents {
+                unchanged.push(manifest.clone());
+            }
+        }
+        changed.retain(|(manifest, _)| !unchanged.contains(manifest));
+
+        let versions: BTreeSet<&String> = released.values().collect();
+        let tags: Vec<String> = match versions.iter().next() {
+            Some(version) if versions.len() == 1 => vec![format!("v{version}")],
+            _ => released
+                .iter()
+                .map(|(name, version)| format!("{name}-v{version}"))
+                .collect(),
+        };
+        let commit = t.commit || t.tag;
+        let message = format!("Release {}", tags.join(", "));
+
+        let lockfile = root.join("Cargo.lock");
+        if t.dry_run || is_dry_run() {
+            for (manifest, _) in &changed {
+                plan(PlannedOp::WriteFile(manifest.clone()));
+            }
+            if lockfile.exists() {
+                plan(PlannedOp::RunCommand(
+                    "cargo update --workspace".to_string(),
+                ));
+            }
+            if commit {
+                plan(PlannedOp::RunCommand(format!(
+                    "git commit -m \"{message}\""
+                )));
+            }
+            if t.tag {
+                for tag in &tags {
+                    plan(PlannedOp::RunCommand(format!("git tag -a {tag}")));
+                }
+            }
+            return Ok(());
+        }
+
+        for (manifest, contents) in &changed {
+            write_atomic(manifest, contents.as_bytes())?;
+        }
+        if lockfile.exists() {
+            cmd!("cargo", "update", "--workspace").dir(&root).run()?;
+        }
+        if commit {
+            let mut files: Vec<&Path> = changed.iter().map(|(m, _)| m.as_path()).collect();
+            if lockfile.exists()
+                && succeeds(&cmd!("git", "ls-files", "--error-unmatch", &lockfile).dir(&root))
+            {
+                files.push(&lockfile);
+            }
+            cmd("git", [Path::new("add")].iter().chain(&files))
+                .dir(&root)
+                .run()?;
+            cmd!("git", "commit", "-m", &message).dir(&root).run()?;
+        }
+        if t.tag {
+            for tag in &tags {
+                cmd!("git", "tag", "-a", tag, "-m", format!("Release {tag}"))
+                    .dir(&root)
+                    .run()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Build a changelog generation with git-cliff
+#[derive(Builder)]
+#[builder(setter(into))]
+pub struct Changelog {
+    /// only include commits since the latest tag (`--unreleased`)
+    /// default: off
+    #[builder(default = "false")]
+    pub unreleased: bool,
+
+    /// version to give the unreleased commits (`--tag`), e.g. `v1.2.0`
+    /// default: none
+    #[builder(default)]
+    pub tag: Option<String>,
+
+    /// file to write
+    /// default: `CHANGELOG.md` in the workspace root
+    #[builder(default)]
+    pub output: Option<PathBuf>,
+}
+
+impl ChangelogBuilder {
+    /// Builds and runs git-cliff
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if git-cliff is missing or failed
+    pub fn run(&self) -> AnyResult<()> {
+        let t = self.build()?;
+        if !succeeds(&cmd!("git-cliff", "--version")) {
+            bail!("git-cliff is not installed, run `cargo install git-cliff`");
+        }
+        let root = get_workspace_root()?;
+        let output = t.output.unwrap_or_else(|| root.join("CHANGELOG.md"));
+        let mut args: Vec<OsString> = vec![];
+        if t.unreleased {
+            args.push("--unreleased".into());
+        }
+        if let Some(tag) = &t.tag {
+            args.extend(["--tag".into(), tag.into()]);
+        }
+        args.extend(["--output".into(), output.into()]);
+        cmd("git-cliff", &args).dir(root).run()?;
+        Ok(())
+    }
+}
+
+///
+/// Regenerate `CHANGELOG.md` from conventional commits with git-cliff
+///
+/// # Errors
+/// Errors if git-cliff is missing or failed
+///
+pub fn changelog() -> AnyResult<()> {
+    ChangelogBuilder::default().run()
+}
+
+/// Documented items counted by `rustdoc --show-coverage`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DocCoverageReport {
+    /// items with a doc comment
+    pub documented: u64,
+    /// items rustdoc counted
+    pub total: u64,
+}
+
+impl DocCoverageReport {
+    /// Parses the `--output-format json` report of `rustdoc --show-coverage`,
+    /// summing all files
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the report is not valid JSON
+    pub fn from_json(report: &str) -> AnyResult<Self> {
+        let files: BTreeMap<String, Value> =
+            serde_json::from_str(report).context("cannot parse rustdoc coverage report")?;
+        let count = |file: &Value, key: &str| file.get(key).and_then(Value::as_u64).unwrap_or(0);
+        Ok(files.values().fold(Self::default(), |acc, file| Self {
+            documented: acc.documented + count(file, "with_docs"),
+            total: acc.total + count(file, "total"),
+        }))
+    }
+
+    /// Share of documented items, in percent (100 when there is nothing to document)
+    #[allow(clippy::cast_precision_loss)]
+    pub fn percent(&self) -> f32 {
+        if self.total == 0 {
+            100.0
+        } else {
+            self.documented as f32 * 100.0 / self.total as f32
+        }
+    }
+}
+
+/// Build a `rustdoc --show-coverage` report (nightly)
+#[derive(Builder)]
+#[builder(setter(into))]
+pub struct DocCoverage {
+    /// package to report on (`-p`)
+    /// default: every workspace member with a library
+    #[builder(default)]
+    pub package: Option<String>,
+
+    /// fail when less than this percentage of items is documented
+    /// default: no limit
+    #[builder(default)]
+    pub min_coverage: Option<f32>,
+}
+
+fn library_packages() -> AnyResult<Vec<String>> {
+    Ok(cargo_metadata()?
+        .members()
+        .filter(|p| p.has_lib())
+        .map(|p| p.name.clone())
+        .collect())
+}
+
+impl DocCoverageBuilder {
+    /// Builds and runs the report, returning the summed counts
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if nightly is not installed or rustdoc failed
+    pub fn run_report(&self) -> AnyResult<DocCoverageReport> {
+        self.build()?.report()
+    }
+
+    /// Builds and runs the report, printing a summary
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if rustdoc failed, or the coverage is
+    /// below `min_coverage`
+    pub fn run(&self) -> AnyResult<()> {
+        let t = self.build()?;
+        let percent = t.report()?.percent();
+        println!("Doc coverage: {percent:.2}%");
+        if let Some(min_coverage) = t.min_coverage {
+            if percent < min_coverage {
+                bail!("doc coverage {percent:.2}% is below the minimum of {min_coverage:.2}%");
+            }
+        }
+        Ok(())
+    }
+}
+
+impl DocCoverage {
+    /// Runs rustdoc for every selected package and sums the counts
+    fn report(&self) -> AnyResult<DocCoverageReport> {
+        if !nightly_available() {
+            bail!(
+                "doc-coverage requires a nightly toolchain, run `rustup toolchain install nightly`"
+            );
+        }
+        let packages = match &self.package {
+            Some(package) => vec![package.clone()],
+            None => library_packages()?,
+        };
+        let mut coverage = DocCoverageReport::default();
+        for package in packages {
+            let report = cmd!(
+                "cargo",
+                "+nightly",
+                "rustdoc",
+                "-p",
+                &package,
+                "--lib",
+                "--",
+                "-Z",
+                "unstable-options",
+                "--show-coverage",
+                "--output-format",
+                "json"
+            )
+            .read()?;
+            let c = DocCoverageReport::from_json(&report)?;
+            println!(
+                "{package}: {}/{} items documented ({:.2}%)",
+                c.documented,
+                c.total,
+                c.percent()
+            );
+            coverage.documented += c.documented;
+            coverage.total += c.total;
+        }
+        Ok(coverage)
+    }
+}
+
+///
+/// Print the share of documented public items (nightly)
+///
+/// # Errors
+/// Errors if nightly is not installed or rustdoc failed
+///
+pub fn doc_coverage() -> AnyResult<()> {
+    DocCoverageBuilder::default().run()
+}
+
+/// Build a `cargo semver-checks` run
+#[derive(Builder)]
+#[builder(setter(into))]
+pub struct SemverChecks {
+    /// package to check (`-p`)
+    /// default: every published workspace member
+    #[builder(default)]
+    pub package: Option<String>,
+
+    /// released version to compare against (`--baseline-version`)
+    /// default: the latest version on crates.io
+    #[builder(default)]
+    pub baseline_version: Option<String>,
+
+    /// git revision to compare against (`--baseline-rev`)
+    /// default: none
+    #[builder(default)]
+    pub baseline_rev: Option<String>,
+}
+
+impl SemverChecksBuilder {
+    /// Builds and runs `cargo semver-checks check-release`
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if cargo-semver-checks is missing, a
+    /// breaking change was found without a matching version bump, or the check
+    /// could not run, e.g. because the baseline cannot be fetched
+    pub fn run(&self) -> AnyResult<()> {
+        let t = self.build()?;
+        if t.baseline_version.is_some() && t.baseline_rev.is_some() {
+            bail!("choose one of `baseline_version` and `baseline_rev`");
+        }
+        require_cargo_subcommand("semver-checks", "cargo-semver-checks")?;
+        let mut args = vec!["semver-checks", "check-release"];
+        match &t.package {
+            Some(package) => args.extend(["-p", package]),
+            None => args.push("--workspace"),
+        }
+        if let Some(version) = &t.baseline_version {
+            args.extend(["--baseline-version", version]);
+        }
+        if let Some(rev) = &t.baseline_rev {
+            args.extend(["--baseline-rev", rev]);
+        }
+        let (code, output) = run_tee("cargo", &args)?;
+        if code == 0 {
+            return Ok(());
+        }
+        // the summary of failed lints reads `semver requires new major version: ...`
+        if output.contains("semver requires new") {
+            bail!("cargo semver-checks found changes that need a bigger version bump, see the output above");
+        }
+        bail!("`cargo semver-checks` failed (exit code {code}), see the output above");
+    }
+}
+
+///
+/// Fail on breaking API changes against the latest release
+///
+/// # Errors
+/// Errors if cargo-semver-checks is missing or found a breaking change
+///
+pub fn semver_checks() -> AnyResult<()> {
+    SemverChecksBuilder::default().run()
+}
+
+/// Build a check against the minimum supported Rust version
+#[derive(Builder)]
+#[builder(setter(into))]
+pub struct Msrv {
+    /// toolchain to check with, e.g. `1.70`
+    /// default: each member's `rust-version`
+    #[builder(default)]
+    pub version: Option<String>,
+}
+
+fn declared_rust_versions() -> AnyResult<BTreeMap<String, Vec<String>>> {
+    let mut versions: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for p in workspace_members()? {
+        if let Some(version) = p.rust_version {
+            versions.entry(version).or_default().push(p.name);
+        }
+    }
+    Ok(versions)
+}
+
+impl MsrvBuilder {
+    /// Builds and runs `cargo +<msrv> check`
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if no MSRV is declared or given, its
+    /// toolchain is not installed, or the code does not build with it
+    pub fn run(&self) -> AnyResult<()> {
+        let t = self.build()?;
+        let checks: Vec<(String, Vec<String>)> = match t.version {
+            Some(version) => vec![(version, vec![])],
+            None => declared_rust_versions()?.into_iter().collect(),
+        };
+        if checks.is_empty() {
+            bail!("no workspace member declares a `rust-version`, set one or pass a version");
+        }
+        for (version, packages) in checks {
+            if !succeeds(&cmd!("rustup", "run", &version, "rustc", "--version")) {
+                bail!("toolchain {version} is not installed, run `rustup toolchain install {version}`");
+            }
+            let toolchain = format!("+{version}");
+            let mut args = vec![toolchain.as_str(), "check", "--all-targets"];
+            if packages.is_empty() {
+                args.push("--workspace");
+            }
+            for package in &packages {
+                args.extend(["-p", package]);
+            }
+            cmd("cargo", &args)
+                .run()
+                .with_context(|| format!("build fails with the declared MSRV {version}"))?;
+        }
+        Ok(())
+    }
+}
+
+///
+/// Check the workspace with the `rust-version` its members declare
+///
+/// # Errors
+/// Errors if no MSRV is declared, its toolchain is missing or the check failed
+///
+pub fn msrv() -> AnyResult<()> {
+    MsrvBuilder::default().run()
+}
+
+/// Build a `cargo udeps` unused dependency check (nightly)
+#[derive(Builder)]
+#[builder(setter(into))]
+pub struct Udeps {
+    /// check with `--all-features`
+    /// default: off
+    #[builder(default = "false")]
+    pub all_features: bool,
+}
+
+impl UdepsBuilder {
+    /// Builds and runs `cargo +nightly udeps --workspace --all-targets`
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if nightly or cargo-udeps is missing,
+    /// an unused dependency was found, or cargo-udeps failed otherwise, e.g. on a
+    /// compile error
+    pub fn run(&self) -> AnyResult<()> {
+        let t = self.build()?;
+        if !nightly_available() {
+            bail!("udeps requires a nightly toolchain, run `rustup toolchain install nightly`");
+        }
+        if !succeeds(&cmd!("cargo", "+nightly", "udeps", "--version")) {
+            bail!("cargo-udeps is not installed, run `cargo install cargo-udeps`");
+        }
+        let mut args = vec!["+nightly", "udeps", "--workspace", "--all-targets"];
+        if t.all_features {
+            args.push("--all-features");
+        }
+        let (code, output) = run_tee("cargo", &args)?;
+        if code == 0 {
+            return Ok(());
+        }
+        if output.contains("unused dependencies:") {
+            bail!("cargo udeps found unused dependencies");
+        }
+        bail!("`cargo udeps` failed (exit code {code}), see the output above");
+    }
+}
+
+///
+/// Fail on unused dependencies in the workspace (nightly)
+///
+/// # Errors
+/// Errors if nightly or cargo-udeps is missing, or a dependency is unused
+///
+pub fn udeps() -> AnyResult<()> {
+    UdepsBuilder::default().run()
+}
+
+/// Build a `cargo bench` run
+#[derive(Builder)]
+#[builder(setter(into))]
+pub struct Bench {
+    /// package to benchmark (`-p`)
+    /// default: the whole workspace
+    #[builder(default)]
+    pub package: Option<String>,
+
+    /// benchmark target to run (`--bench`)
+    /// default: all benchmarks
+    #[builder(default)]
+    pub bench_name: Option<String>,
+
+    /// criterion baseline to compare against (`-- --baseline`)
+    /// default: none
+    #[builder(default)]
+    pub baseline: Option<String>,
+
+    /// criterion baseline to save the results as (`-- --save-baseline`)
+    /// default: none
+    #[builder(default)]
+    pub save_baseline: Option<String>,
+}
+
+impl BenchBuilder {
+    /// Builds and runs `cargo bench`
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if a benchmark failed to build or run
+    pub fn run(&self) -> AnyResult<()> {
+        let t = self.build()?;
+        let mut args = vec!["bench"];
+        match &t.package {
+            Some(package) => args.extend(["-p", package]),
+            None => args.push("--workspace"),
+        }
+        if let Some(bench) = &t.bench_name {
+            args.extend(["--bench", bench]);
+        }
+        let mut harness = vec![];
+        if let Some(baseline) = &t.baseline {
+            harness.extend(["--baseline", baseline]);
+        }
+        if let Some(baseline) = &t.save_baseline {
+            harness.extend(["--save-baseline", baseline]);
+        }
+        if !harness.is_empty() {
+            args.push("--");
+            args.extend(harness);
+        }
+        cmd("cargo", &args).run()?;
+        Ok(())
+    }
+}
+
+///
+/// Run all benchmarks in the workspace
+///
+/// # Errors
+/// Errors if a benchmark failed to build or run
+///
+pub fn bench() -> AnyResult<()> {
+    BenchBuilder::default().run()
+}
+
+/// Build a `cargo flamegraph` profile
+#[derive(Builder)]
+#[builder(setter(into))]
+pub struct Flamegraph {
+    /// binary to profile (`--bin`)
+    /// default: the package's only binary
+    #[builder(default)]
+    pub bin: Option<String>,
+
+    /// example to profile (`--example`)
+    /// default: none
+    #[builder(default)]
+    pub example: Option<String>,
+
+    /// benchmark to profile (`--bench`)
+    /// default: none
+    #[builder(default)]
+    pub bench: Option<String>,
+
+    /// arguments passed to the profiled program, after `--`
+    /// default: none
+    #[builder(default)]
+    pub args: Vec<String>,
+
+    /// svg file to write
+    /// default: `flamegraph.svg` in the project root
+    #[builder(default)]
+    pub output: Option<PathBuf>,
+}
+
+impl FlamegraphBuilder {
+    /// Builds and runs `cargo flamegraph`
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if flamegraph or the system profiler is
+    /// missing, or the profiled program failed
+    pub fn run(&self) -> AnyResult<()> {
+        let t = self.build()?;
+        let targets = [
+            ("--bin", &t.bin),
+            ("--example", &t.example),
+            ("--bench", &t.bench),
+        ];
+        if targets.iter().filter(|(_, name)| name.is_some()).count() > 1 {
+            bail!("choose one of `bin`, `example` and `bench`");
+        }
+        require_cargo_subcommand("flamegraph", "flamegraph")?;
+        let (profiler, probe) = if cfg!(target_os = "macos") {
+            ("dtrace", cmd!("dtrace", "-V"))
+        } else {
+            ("perf", cmd!("perf", "--version"))
+        };
+        if !cfg!(windows) && !succeeds(&probe) {
+            bail!("flamegraph needs `{profiler}`, install it with your system's package manager");
+        }
+
+        let output = match t.output {
+            Some(output) => output,
+            None => nearest_cargo_dir()?.join("flamegraph.svg"),
+        };
+        let mut args: Vec<OsString> = vec!["flamegraph".into()];
+        for (flag, name) in targets {
+            if let Some(name) = name {
+                args.extend([flag.into(), name.into()]);
+            }
+        }
+        args.extend(["--output".into(), output.into()]);
+        if !t.args.is_empty() {
+            args.push("--".into());
+            args.extend(t.args.iter().map(OsString::from));
+        }
+        cmd("cargo", &args).run()?;
+        Ok(())
+    }
+}
+
+///
+/// Profile the package's binary and write `flamegraph.svg`
+///
+/// # Errors
+/// Errors if flamegraph or the system profiler is missing, or the program failed
+///
+pub fn flamegraph() -> AnyResult<()> {
+    FlamegraphBuilder::default().run()
+}
+
+///
+/// Show biggest crates in release build
+///
+/// # Errors
+/// Errors if the command failed
+///
+pub fn bloat_deps(package: &str) -> AnyResult<()> {
+    cmd!("cargo", "bloat", "--release", "--crates", "-p", package).run()?;
+    Ok(())
+}
+
+///
+/// Show the `count` biggest functions in release build
+///
+/// # Errors
+/// Errors if the command failed
+///
+pub fn bloat_functions(package: &str, count: usize) -> AnyResult<()> {
+    cmd!(
+        "cargo",
+        "bloat",
+        "--release",
+        "-n",
+        count.to_string(),
+        "-p",
+        package
+    )
+    .run()?;
+    Ok(())
+}
+
+///
+/// Show crate build times
+///
+/// # Errors
+/// Errors if the command failed
+///
+pub fn bloat_time(package: &str) -> AnyResult<()> {
+    cmd!("cargo", "bloat", "--time", "-j", "1", "-p", package).run()?;
+    Ok(())
+}
+
+fn bloat_json(args: &[&str]) -> AnyResult<String> {
+    let output = run_captured(
+        "cargo",
+        [&["bloat"], args, &["--message-format", "json"]].concat(),
+    )?;
+    if !output.success() {
+        bail!(
+            "cargo bloat failed ({}):\n{}",
+            output.status,
+            output.stderr.trim_end()
+        );
+    }
+    Ok(output.stdout)
+}
+
+///
+/// Biggest crates in release build, as cargo-bloat's JSON output
+///
+/// # Errors
+/// Errors if the command failed
+///
+pub fn bloat_deps_json(package: &str) -> AnyResult<String> {
+    bloat_json(&["--release", "--crates", "-p", package])
+}
+
+///
+/// The `count` biggest functions in release build, as cargo-bloat's JSON output
+///
+/// # Errors
+/// Errors if the command failed
+///
+pub fn bloat_functions_json(package: &str, count: usize) -> AnyResult<String> {
+    bloat_json(&["--release", "-n", &count.to_string(), "-p", package])
+}
+
+///
+/// Crate build times, as cargo-bloat's JSON output
+///
+/// # Errors
+/// Errors if the command failed
+///
+pub fn bloat_time_json(package: &str) -> AnyResult<String> {
+    bloat_json(&["--time", "-j", "1", "-p", package])
+}
+
+/// Build a `cargo watch` loop
+#[derive(Builder)]
+#[builder(setter(into))]
+pub struct Dev {
+    /// cargo commands to run in order after every change (`-x`), e.g. `test --lib`.
+    /// A failing command skips the rest.
+    /// default: `check`, then `test`
+    #[builder(default = r#"vec!["check".to_string(), "test".to_string()]"#)]
+    pub execs: Vec<String>,
+
+    /// paths or globs to ignore (`-i`)
+    /// default: none
+    #[builder(default)]
+    pub ignore: Vec<String>,
+
+    /// clear the screen before each run (`-c`)
+    /// default: off
+    #[builder(default = "false")]
+    pub clear: bool,
+}
+
+impl DevBuilder {
+    /// Builds and runs `cargo watch`
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if run failed
+    pub fn run(&self) -> AnyResult<()> {
+        let t = self.build()?;
+        let mut args = vec!["watch"];
+        if t.clear {
+            args.push("-c");
+        }
+        for path in &t.ignore {
+            args.extend(["-i", path]);
+        }
+        for exec in &t.execs {
+            args.extend(["-x", exec]);
+        }
+        cmd("cargo", &args).run()?;
+        Ok(())
+    }
+}
+
+///
+/// Watch changes and after every change: `cargo check`, followed by `cargo test`
+/// If `cargo check` fails, tests will not run.
+///
+/// # Errors
+/// Errors if the command failed
+///
+pub fn dev() -> AnyResult<()> {
+    DevBuilder::default().run()
+}
+
+/// A tool used by the tasks, installed by `install`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Tool {
+    /// `cargo-watch`, for `dev` and `docs`
+    Watch,
+    /// `cargo-hack`, for `powerset` and `each_feature`
+    Hack,
+    /// `cargo-bloat`, for `bloat_deps` and `bloat_time`
+    Bloat,
+    /// the `llvm-tools-preview` rustup component, for coverage
+    LlvmTools,
+    /// `grcov`, for coverage
+    Grcov,
+    /// `taplo-cli`, for `fmt_toml`
+    Taplo,
+    /// `cargo-geiger`, for `geiger`
+    Geiger,
+    /// `cargo-audit`, for `audit`
+    Audit,
+    /// `cargo-deny`, for `deny`
+    Deny,
+    /// `cargo-outdated`, for `outdated`
+    Outdated,
+    /// `git-cliff`, for `changelog`
+    GitCliff,
+    /// `flamegraph`, for `flamegraph`
+    Flamegraph,
+    /// `typos-cli`, for `spellcheck`
+    Typos,
+    /// `cargo-semver-checks`, for `semver_checks`
+    SemverChecks,
+    /// `cargo-udeps`, for `udeps`
+    Udeps,
+}
+
+impl Tool {
+    /// All tools
+    pub const ALL: [Self; 15] = [
+        Self::Watch,
+        Self::Hack,
+        Self::Bloat,
+        Self::LlvmTools,
+        Self::Grcov,
+        Self::Taplo,
+        Self::Geiger,
+        Self::Audit,
+        Self::Deny,
+        Self::Outdated,
+        Self::GitCliff,
+        Self::Flamegraph,
+        Self::Typos,
+        Self::SemverChecks,
+        Self::Udeps,
+    ];
+
+    /// Tools installed when none are selected
+    pub const DEFAULT: [Self; 6] = [
+        Self::Watch,
+        Self::Hack,
+        Self::Bloat,
+        Self::LlvmTools,
+        Self::Grcov,
+        Self::Outdated,
+    ];
+
+    /// Name of the tool, as accepted by `from_str`
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Watch => "watch",
+            Self::Hack => "hack",
+            Self::Bloat => "bloat",
+            Self::LlvmTools => "llvm-tools",
+            Self::Grcov => "grcov",
+            Self::Taplo => "taplo",
+            Self::Geiger => "geiger",
+            Self::Audit => "audit",
+            Self::Deny => "deny",
+            Self::Outdated => "outdated",
+            Self::GitCliff => "git-cliff",
+            Self::Flamegraph => "flamegraph",
+            Self::Typos => "typos",
+            Self::SemverChecks => "semver-checks",
+            Self::Udeps => "udeps",
+        }
+    }
+
+    /// Crate to `cargo install`, `None` for rustup components
+    pub fn crate_name(self) -> Option<&'static str> {
+        match self {
+            Self::Watch => Some("cargo-watch"),
+            Self::Hack => Some("cargo-hack"),
+            Self::Bloat => Some("cargo-bloat"),
+            Self::LlvmTools => None,
+            Self::Grcov => Some("grcov"),
+            Self::Taplo => Some("taplo-cli"),
+            Self::Geiger => Some("cargo-geiger"),
+            Self::Audit => Some("cargo-audit"),
+            Self::Deny => Some("cargo-deny"),
+            Self::Outdated => Some("cargo-outdated"),
+            Self::GitCliff => Some("git-cliff"),
+            Self::Flamegraph => Some("flamegraph"),
+            Self::Typos => Some("typos-cli"),
+            Self::SemverChecks => Some("cargo-semver-checks"),
+            Self::Udeps => Some("cargo-udeps"),
+        }
+    }
+}
+
+impl FromStr for Tool {
+    type Err = XtaskError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|t| t.as_str() == s)
+            .ok_or_else(|| {
+                XtaskError::InvalidArgument(format!(
+                    "unknown tool `{s}`, expected one of: {}",
+                    Self::ALL.map(Self::as_str).join(", ")
+                ))
+            })
+    }
+}
+
+/// Build an installation of the cargo tools the tasks rely on
+#[derive(Builder)]
+#[builder(setter(into))]
+pub struct Install {
+    /// tools to install
+    /// default: `Tool::DEFAULT`
+    #[builder(default = "Tool::DEFAULT.to_vec()")]
+    pub tools: Vec<Tool>,
+
+    /// reinstall tools that are already installed (`cargo install --force`)
+    /// default: off
+    #[builder(default = "false")]
+    pub force: bool,
+
+    /// fetch prebuilt binaries with `cargo binstall` when it is installed,
+    /// falling back to `cargo install` for tools it cannot install
+    /// default: off
+    #[builder(default = "false")]
+    pub use_binstall: bool,
+
+    /// versions to pin tools to (`--version`), e.g. `Tool::Grcov => "0.8.13"`.
+    /// A tool installed at another version is replaced.
+    /// default: latest
+    #[builder(default)]
+    pub versions: HashMap<Tool, String>,
+
+    /// build with the tools' own `Cargo.lock` (`--locked`)
+    /// default: on
+    #[builder(default = "true")]
+    pub locked: bool,
+
+    /// how often to retry a failed `cargo install`, e.g. on a flaky network
+    /// default: 0
+    #[builder(default = "0")]
+    pub retries: usize,
+}
+
+/// Crates listed by `cargo install --list`, with their versions
+fn installed_crates() -> AnyResult<BTreeMap<String, String>> {
+    let list = cmd!("cargo", "install", "--list").read()?;
+    // crate lines look like `grcov v0.8.19:`, their binaries follow indented
+    Ok(list
+        .lines()
+        .filter(|line| !line.starts_with(char::is_whitespace))
+        .filter_map(|line| {
+            let mut parts = line.split_whitespace();
+            let name = parts.next()?;
+            let version = parts.next()?.trim_start_matches('v').trim_end_matches(':');
+            Some((name.to_string(), version.to_string()))
+        })
+        .collect())
+}
+
+impl Install {
+    /// `cargo install` arguments for `tool`
+    fn install_args(&self, tool: Tool) -> Vec<&str> {
+        let mut args = vec!["install"];
+        args.extend(tool.crate_name());
+        if let Some(version) = self.versions.get(&tool) {
+            args.extend(["--version", version]);
+        }
+        if self.locked {
+            args.push("--locked");
+        }
+        if self.force {
+            args.push("--force");
+        }
+        args
+    }
+}
+
+impl InstallBuilder {
+    /// Builds and runs the installation
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if run failed
+    pub fn run(&self) -> AnyResult<()> {
+        let t = self.build()?;
+        if t.tools.contains(&Tool::LlvmTools) {
+            cmd!("rustup", "component", "add", "llvm-tools-preview").run()?;
+        }
+        let installed = if t.force {
+            BTreeMap::new()
+        } else {
+            installed_crates()?
+        };
+        let binstall = t.use_binstall && succeeds(&cmd!("cargo", "binstall", "-V"));
+        if t.use_binstall && !binstall {
+            status(
+                "cargo-binstall not found, building tools with cargo install",
+                "33",
+            );
+        }
+        for &tool in &t.tools {
+            let Some(name) = tool.crate_name() else {
+                continue;
+            };
+            let pinned = t.versions.get(&tool);
+            if let Some(version) = installed.get(name) {
+                if pinned.is_none_or(|pinned| pinned == version) {
+                    status(&format!("{name}: already installed"), "1");
+                    continue;
+                }
+            }
+            if binstall {
+                let spec = pinned.map_or_else(|| name.to_string(), |v| format!("{name}@{v}"));
+                let mut args = vec!["binstall", "-y", spec.as_str()];
+                if t.locked {
+                    args.push("--locked");
+                }
+                if t.force {
+                    args.push("--force");
+                }
+                if cmd("cargo", &args).unchecked().run()?.status.success() {
+                    continue;
+                }
+            }
+            run_with_retries(
+                "cargo",
+                &t.install_args(tool),
+                t.retries,
+                Duration::from_secs(5),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+///
+/// Instal cargo tools, skipping the ones already installed
+///
+/// # Errors
+/// Errors if one of the commands failed
+///
+pub fn install() -> AnyResult<()> {
+    InstallBuilder::default().run()
+}
+
+///
+/// Update installed cargo tools with `cargo install-update` (from `cargo-update`).
+/// Without `cargo-update`, reinstall them instead.
+/// An empty `tools` updates every installed crate with `cargo install-update -a`
+/// when `cargo-update` is installed, and [`Tool::DEFAULT`] otherwise.
+///
+/// # Errors
+/// Errors if one of the commands failed
+///
+pub fn update_tools(tools: &[Tool]) -> AnyResult<()> {
+    if succeeds(&cmd!("cargo", "install-update", "--version")) {
+        let mut args = vec!["install-update"];
+        if tools.is_empty() {
+            args.push("-a");
+        } else {
+            // rustup components such as llvm-tools are updated with the toolchain
+            let crates: Vec<&str> = tools.iter().filter_map(|tool| tool.crate_name()).collect();
+            if crates.is_empty() {
+                return Ok(());
+            }
+            args.extend(crates);
+        }
+        cmd("cargo", &args).run()?;
+    } else {
+        let mut install = InstallBuilder::default();
+        install.force(true);
+        if !tools.is_empty() {
+            install.tools(tools.to_vec());
+        }
+        install.run()?;
+    }
+    Ok(())
+}
+
+/// Set up a main for your xtask. Uses clap.
 /// To customize, look at this function's source and copy it to your
 /// own xtask project.
 ///
@@ -280,28 +3647,551 @@ pub fn install() -> AnyResult<()> {
 #[cfg(feature = "clap")]
 pub fn main() -> AnyResult<()> {
     use clap::{AppSettings, Arg, Command};
+    let jobs_arg = Arg::new("jobs")
+        .short('j')
+        .long("jobs")
+        .help("number of parallel jobs")
+        .takes_value(true)
+        .value_parser(clap::value_parser!(usize));
     let cli = Command::new("xtask")
         .setting(AppSettings::SubcommandRequiredElseHelp)
+        .arg(
+            Arg::new("color")
+                .long("color")
+                .help("when to color output")
+                .possible_values(["auto", "always", "never"])
+                .default_value("auto")
+                .global(true)
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("quiet")
+                .short('q')
+                .long("quiet")
+                .help("ask cargo for less output")
+                .conflicts_with("verbose")
+                .global(true),
+        )
+        .arg(
+            Arg::new("verbose")
+                .short('v')
+                .long("verbose")
+                .help("ask cargo for more output")
+                .global(true),
+        )
+        .arg(
+            Arg::new("yes")
+                .short('y')
+                .long("yes")
+                .help("answer yes to confirmation prompts")
+                .global(true),
+        )
         .subcommand(
-            Command::new("coverage").arg(
-                Arg::new("fmt")
+            Command::new("coverage")
+                .arg(
+                    Arg::new("fmt")
+                        .short('f')
+                        .long("format")
+                        .help("choose the format in which the coverage files are generated")
+                        .possible_values(CoverageFormat::ALL.map(CoverageFormat::as_str))
+                        .takes_value(true),
+                )
+                .arg(jobs_arg.clone())
+                .arg(
+                    Arg::new("open")
+                        .long("open")
+                        .help("open the html report in the browser"),
+                )
+                .arg(
+                    Arg::new("min-coverage")
+                        .long("min-coverage")
+                        .help("fail when total line coverage, in percent, is below this")
+                        .takes_value(true)
+                        .value_parser(clap::value_parser!(f32)),
+                )
+                .arg(
+                    Arg::new("backend")
+                        .long("backend")
+                        .help("coverage tool to use")
+                        .possible_values(["grcov", "llvm-cov"])
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("package")
+                        .short('p')
+                        .long("package")
+                        .help("only measure this package")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            Command::new("cobertura_total_coverage").arg(
+                Arg::new("file")
                     .short('f')
-                    .long("format")
-                    .help("choose the format in which the coverage files are generated.\n Valid options are [html,lcov,profraw]")
+                    .long("file")
+                    .help("Set cobertura file")
                     .takes_value(true),
             ),
-        ).subcommand(
-            Command::new("cobertura_total_coverage").arg(
+        )
+        .subcommand(
+            Command::new("coverage-baseline").arg(
                 Arg::new("file")
                     .short('f')
                     .long("file")
-                    .help("Set cobertura file")
+                    .help("baseline file to write")
+                    .default_value("coverage-baseline.txt")
                     .takes_value(true),
             ),
         )
+        .subcommand(
+            Command::new("coverage-diff")
+                .arg(
+                    Arg::new("file")
+                        .short('f')
+                        .long("file")
+                        .help("baseline file to compare against")
+                        .default_value("coverage-baseline.txt")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("base")
+                        .long("base")
+                        .help("git revision to compare against instead of a baseline file, e.g. origin/main")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("tolerance")
+                        .short('t')
+                        .long("tolerance")
+                        .help("allowed coverage drop, in percentage points")
+                        .takes_value(true)
+                        .value_parser(clap::value_parser!(f32)),
+                ),
+        )
         .subcommand(Command::new("vars"))
-        .subcommand(Command::new("ci"))
-        .subcommand(Command::new("powerset"))
+        .subcommand(
+            Command::new("ci")
+                .arg(jobs_arg)
+                .arg(
+                    Arg::new("nextest")
+                        .long("nextest")
+                        .help("run tests with cargo-nextest"),
+                )
+                .arg(
+                    Arg::new("toolchain")
+                        .long("toolchain")
+                        .help("toolchain to run every cargo command with, e.g. beta or 1.75.0")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("features")
+                        .long("features")
+                        .help("features to enable for clippy and tests")
+                        .multiple_values(true)
+                        .use_value_delimiter(true)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("all-features")
+                        .long("all-features")
+                        .help("enable all features for clippy and tests"),
+                )
+                .arg(
+                    Arg::new("no-default-features")
+                        .long("no-default-features")
+                        .help("disable default features for clippy and tests"),
+                )
+                .arg(
+                    Arg::new("keep-going")
+                        .long("keep-going")
+                        .help("run all steps and report every failure"),
+                )
+                .arg(
+                    Arg::new("skip")
+                        .long("skip")
+                        .help("steps to leave out")
+                        .possible_values(["fmt", "clippy", "tests", "doc-tests"])
+                        .multiple_values(true)
+                        .use_value_delimiter(true)
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(Command::new("check-examples"))
+        .subcommand(Command::new("test-unit"))
+        .subcommand(Command::new("test-integration"))
+        .subcommand(
+            Command::new("slow-tests").arg(
+                Arg::new("threshold")
+                    .short('t')
+                    .long("threshold")
+                    .help("report tests slower than this many milliseconds")
+                    .default_value("1000")
+                    .takes_value(true)
+                    .value_parser(clap::value_parser!(u64)),
+            ),
+        )
+        .subcommand(
+            Command::new("powerset")
+                .arg(
+                    Arg::new("features")
+                        .long("features")
+                        .help("comma separated feature combination to run instead of the powerset, repeatable")
+                        .multiple_occurrences(true)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("skip")
+                        .long("skip")
+                        .help("commands to leave out")
+                        .possible_values(["clippy", "tests", "doc-tests"])
+                        .multiple_values(true)
+                        .use_value_delimiter(true)
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(Command::new("each-feature"))
+        .subcommand(Command::new("check-no-default-features"))
+        .subcommand(
+            Command::new("check-changed").arg(
+                Arg::new("base")
+                    .long("base")
+                    .help("git revision to compare against")
+                    .default_value("HEAD")
+                    .takes_value(true),
+            ),
+        )
+        .subcommand(
+            Command::new("for-each-member")
+                .trailing_var_arg(true)
+                .arg(
+                    Arg::new("command")
+                        .help("cargo command to run in every member")
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("args")
+                        .help("arguments passed to the command, after `--` if they start with `-`")
+                        .multiple_values(true)
+                        .allow_hyphen_values(true),
+                ),
+        )
+        .subcommand(Command::new("minimal-versions"))
+        .subcommand(
+            Command::new("dep-consistency").arg(
+                Arg::new("deny")
+                    .long("deny")
+                    .help("fail when members use different versions of a dependency"),
+            ),
+        )
+        .subcommand(
+            Command::new("deps-snapshot").arg(
+                Arg::new("file")
+                    .short('f')
+                    .long("file")
+                    .help("snapshot file to write")
+                    .default_value("deps.txt")
+                    .takes_value(true),
+            ),
+        )
+        .subcommand(
+            Command::new("deps-check").arg(
+                Arg::new("file")
+                    .short('f')
+                    .long("file")
+                    .help("snapshot file to compare against")
+                    .default_value("deps.txt")
+                    .takes_value(true),
+            ),
+        )
+        .subcommand(
+            Command::new("doc-fences").arg(
+                Arg::new("deny")
+                    .long("deny")
+                    .help("fail when an ignored or no_run doc example is found"),
+            ),
+        )
+        .subcommand(
+            Command::new("forbidden").arg(
+                Arg::new("pattern")
+                    .help("tokens to reject, defaults to dbg!(, todo!( and unimplemented!(")
+                    .multiple_values(true),
+            ),
+        )
+        .subcommand(
+            Command::new("fmt-toml").arg(
+                Arg::new("check")
+                    .long("check")
+                    .help("only check formatting, don't write"),
+            ),
+        )
+        .subcommand(
+            Command::new("spellcheck").arg(
+                Arg::new("write")
+                    .short('w')
+                    .long("write")
+                    .help("fix typos in place"),
+            ),
+        )
+        .subcommand(
+            Command::new("fuzz")
+                .arg(Arg::new("target").help("fuzz target to run, lists targets when omitted"))
+                .arg(
+                    Arg::new("duration")
+                        .short('d')
+                        .long("duration")
+                        .help("maximum fuzzing time in seconds")
+                        .default_value("60")
+                        .takes_value(true)
+                        .value_parser(clap::value_parser!(u64)),
+                ),
+        )
+        .subcommand(
+            Command::new("geiger").arg(
+                Arg::new("max-unsafe")
+                    .long("max-unsafe")
+                    .help("fail when the workspace crates use unsafe more often than this")
+                    .takes_value(true)
+                    .value_parser(clap::value_parser!(u64)),
+            ),
+        )
+        .subcommand(
+            Command::new("audit")
+                .arg(
+                    Arg::new("deny-warnings")
+                        .long("deny-warnings")
+                        .help("fail on unmaintained or yanked crates too"),
+                )
+                .arg(
+                    Arg::new("ignore")
+                        .long("ignore")
+                        .help("advisory IDs to ignore")
+                        .multiple_occurrences(true)
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            Command::new("deny").arg(
+                Arg::new("check")
+                    .help("checks to run, defaults to all")
+                    .possible_values(DENY_CHECKS)
+                    .multiple_values(true),
+            ),
+        )
+        .subcommand(
+            Command::new("outdated")
+                .arg(
+                    Arg::new("workspace")
+                        .long("workspace")
+                        .help("report on every workspace member"),
+                )
+                .arg(
+                    Arg::new("exit-code")
+                        .long("exit-code")
+                        .help("fail when a dependency is outdated"),
+                ),
+        )
+        .subcommand(
+            Command::new("release")
+                .arg(
+                    Arg::new("version")
+                        .help("major, minor, patch or an explicit version")
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("package")
+                        .short('p')
+                        .long("package")
+                        .help("packages to release, defaults to all members")
+                        .multiple_occurrences(true)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("commit")
+                        .long("commit")
+                        .help("commit the version changes"),
+                )
+                .arg(
+                    Arg::new("tag")
+                        .long("tag")
+                        .help("commit and create an annotated tag"),
+                )
+                .arg(
+                    Arg::new("dry-run")
+                        .long("dry-run")
+                        .help("only print the planned changes"),
+                ),
+        )
+        .subcommand(
+            Command::new("changelog")
+                .arg(
+                    Arg::new("unreleased")
+                        .long("unreleased")
+                        .help("only include commits since the latest tag"),
+                )
+                .arg(
+                    Arg::new("tag")
+                        .long("tag")
+                        .help("version to give the unreleased commits")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .help("file to write, defaults to CHANGELOG.md in the workspace root")
+                        .takes_value(true)
+                        .value_parser(clap::value_parser!(PathBuf)),
+                ),
+        )
+        .subcommand(
+            Command::new("doc-coverage")
+                .arg(
+                    Arg::new("package")
+                        .short('p')
+                        .long("package")
+                        .help("package to report on")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("min-coverage")
+                        .long("min-coverage")
+                        .help("fail below this percentage of documented items")
+                        .takes_value(true)
+                        .value_parser(clap::value_parser!(f32)),
+                ),
+        )
+        .subcommand(
+            Command::new("semver-checks")
+                .arg(
+                    Arg::new("package")
+                        .short('p')
+                        .long("package")
+                        .help("package to check")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("baseline-version")
+                        .long("baseline-version")
+                        .help("released version to compare against")
+                        .takes_value(true)
+                        .conflicts_with("baseline-rev"),
+                )
+                .arg(
+                    Arg::new("baseline-rev")
+                        .long("baseline-rev")
+                        .help("git revision to compare against")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            Command::new("msrv").arg(
+                Arg::new("version")
+                    .help("toolchain to check with, defaults to the declared `rust-version`"),
+            ),
+        )
+        .subcommand(
+            Command::new("udeps").arg(
+                Arg::new("all-features")
+                    .long("all-features")
+                    .help("check with all features enabled"),
+            ),
+        )
+        .subcommand(
+            Command::new("bench")
+                .arg(
+                    Arg::new("package")
+                        .short('p')
+                        .long("package")
+                        .help("package to benchmark")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("bench")
+                        .long("bench")
+                        .help("benchmark target to run")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("baseline")
+                        .long("baseline")
+                        .help("criterion baseline to compare against")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("save-baseline")
+                        .long("save-baseline")
+                        .help("criterion baseline to save the results as")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            Command::new("flamegraph")
+                .trailing_var_arg(true)
+                .arg(
+                    Arg::new("bin")
+                        .long("bin")
+                        .help("binary to profile")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("example")
+                        .long("example")
+                        .help("example to profile")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("bench")
+                        .long("bench")
+                        .help("benchmark to profile")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .help("svg file to write")
+                        .takes_value(true)
+                        .value_parser(clap::value_parser!(PathBuf)),
+                )
+                .arg(
+                    Arg::new("args")
+                        .help("arguments for the profiled program, after `--`")
+                        .multiple_values(true)
+                        .allow_hyphen_values(true),
+                ),
+        )
+        .subcommand(
+            Command::new("licenses")
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .help("report format")
+                        .possible_values(["text", "json", "tsv"])
+                        .default_value("text")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .help("write the report to this file")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            Command::new("miri")
+                .arg(
+                    Arg::new("flags")
+                        .long("flags")
+                        .help("flags passed to miri through MIRIFLAGS")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("install")
+                        .long("install")
+                        .help("install the miri component if it is missing"),
+                ),
+        )
         .subcommand(
             Command::new("bloat-deps").arg(
                 Arg::new("package")
@@ -312,6 +4202,25 @@ pub fn main() -> AnyResult<()> {
                     .takes_value(true),
             ),
         )
+        .subcommand(
+            Command::new("bloat-functions")
+                .arg(
+                    Arg::new("package")
+                        .short('p')
+                        .long("package")
+                        .help("package to build")
+                        .required(true)
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::new("count")
+                        .short('n')
+                        .help("number of functions to show")
+                        .default_value("20")
+                        .takes_value(true)
+                        .value_parser(clap::value_parser!(usize)),
+                ),
+        )
         .subcommand(
             Command::new("bloat-time").arg(
                 Arg::new("package")
@@ -322,30 +4231,358 @@ pub fn main() -> AnyResult<()> {
                     .takes_value(true),
             ),
         )
-        .subcommand(Command::new("docs"));
+        .subcommand(
+            Command::new("docs")
+                .arg(
+                    Arg::new("once")
+                        .long("once")
+                        .help("build once instead of watching for changes"),
+                )
+                .arg(
+                    Arg::new("all-features")
+                        .long("all-features")
+                        .help("document with all features enabled"),
+                )
+                .arg(
+                    Arg::new("private")
+                        .long("document-private-items")
+                        .help("include private items"),
+                )
+                .arg(
+                    Arg::new("open")
+                        .long("open")
+                        .help("open the docs in the browser (with --once)"),
+                )
+                .arg(
+                    Arg::new("deny-broken-links")
+                        .long("deny-broken-links")
+                        .help("fail on broken intra-doc links"),
+                ),
+        )
+        .subcommand(
+            Command::new("install")
+                .arg(
+                    Arg::new("tool")
+                        .help("tools to install, defaults to watch, hack, bloat, llvm-tools, grcov and outdated")
+                        .possible_values(Tool::ALL.map(Tool::as_str))
+                        .multiple_values(true),
+                )
+                .arg(
+                    Arg::new("force")
+                        .long("force")
+                        .help("reinstall tools that are already installed"),
+                )
+                .arg(
+                    Arg::new("binstall")
+                        .long("binstall")
+                        .help("use prebuilt binaries from cargo-binstall when available"),
+                )
+                .arg(
+                    Arg::new("retries")
+                        .long("retries")
+                        .help("how often to retry a failed install")
+                        .takes_value(true)
+                        .value_parser(clap::value_parser!(usize)),
+                ),
+        )
+        .subcommand(
+            Command::new("update-tools").arg(
+                Arg::new("tool")
+                    .help("tools to update, defaults to all")
+                    .possible_values(Tool::ALL.map(Tool::as_str))
+                    .multiple_values(true),
+            ),
+        );
     let matches = cli.get_matches();
+    if let Some(color) = matches.get_one::<String>("color") {
+        crate::ops::set_color(color.parse()?);
+    }
+    crate::ops::set_assume_yes(matches.contains_id("yes"));
+    if matches.contains_id("quiet") {
+        crate::ops::set_verbosity(crate::ops::Verbosity::Quiet);
+    } else if matches.contains_id("verbose") {
+        crate::ops::set_verbosity(crate::ops::Verbosity::Verbose);
+    }
 
-    let root = crate::ops::root_dir();
     let res = match matches.subcommand() {
-        Some(("coverage", sm)) => crate::tasks::coverage(
-            sm.get_one::<String>("fmt")
-                .context("please provide an output file format")?,
-        ),
+        Some(("coverage", sm)) => {
+            let config = XtaskConfig::load()?;
+            let mut builder = CoverageBuilder::default();
+            builder.with_config(&config);
+            if let Some(fmt) = sm.get_one::<String>("fmt") {
+                builder.fmt(fmt.parse::<CoverageFormat>()?);
+            } else if config.coverage_format.is_none() {
+                anyhow::bail!("please provide an output file format");
+            }
+            if let Some(jobs) = sm.get_one::<usize>("jobs") {
+                builder.jobs(*jobs);
+            }
+            builder.package(sm.get_one::<String>("package").cloned());
+            builder.open(sm.contains_id("open"));
+            if let Some(min_coverage) = sm.get_one::<f32>("min-coverage") {
+                builder.min_coverage(*min_coverage);
+            }
+            if let Some(backend) = sm.get_one::<String>("backend") {
+                builder.backend(backend.parse::<CoverageBackend>()?);
+            }
+            builder.run()
+        }
         Some(("cobertura_total_coverage", sm)) => crate::tasks::cobertura_total_coverage(
             sm.get_one::<String>("file")
                 .context("please provide an input file ")?,
         ),
+        Some(("coverage-baseline", sm)) => crate::tasks::save_coverage_baseline(
+            sm.get_one::<String>("file")
+                .context("please provide a baseline file")?,
+        ),
+        Some(("coverage-diff", sm)) => {
+            let tolerance = match sm.get_one::<f32>("tolerance") {
+                Some(tolerance) => Some(*tolerance),
+                None => XtaskConfig::load()?.coverage_tolerance,
+            };
+            match sm.get_one::<String>("base") {
+                Some(base) => crate::tasks::coverage_diff_ref(base, tolerance),
+                None => crate::tasks::coverage_diff(
+                    sm.get_one::<String>("file")
+                        .context("please provide a baseline file")?,
+                    tolerance.unwrap_or_default(),
+                ),
+            }
+        }
         Some(("vars", _)) => {
+            let root = crate::ops::root_dir()?;
             println!("root: {root:?}");
             Ok(())
         }
-        Some(("ci", _)) => crate::tasks::ci(),
-        Some(("docs", _)) => crate::tasks::docs(),
-        Some(("powerset", _)) => crate::tasks::powerset(),
+        Some(("ci", sm)) => {
+            let config = XtaskConfig::load()?;
+            let mut builder = CIBuilder::default();
+            builder.with_config(&config);
+            if let Some(jobs) = sm.get_one::<usize>("jobs") {
+                builder.jobs(*jobs);
+            }
+            if sm.contains_id("nextest") {
+                builder.nextest(true);
+            }
+            if let Some(toolchain) = sm.get_one::<String>("toolchain") {
+                builder.toolchain(toolchain.clone());
+            }
+            if let Some(features) = sm.get_many::<String>("features") {
+                builder.features(features.cloned().collect::<Vec<_>>());
+            }
+            builder.all_features(sm.contains_id("all-features"));
+            builder.no_default_features(sm.contains_id("no-default-features"));
+            if sm.contains_id("keep-going") {
+                builder.fail_fast(false);
+            }
+            for step in sm.get_many::<String>("skip").into_iter().flatten() {
+                match step.as_str() {
+                    "fmt" => builder.run_fmt(false),
+                    "clippy" => builder.run_clippy(false),
+                    "tests" => builder.run_tests(false),
+                    _ => builder.run_doc_tests(false),
+                };
+            }
+            builder.run()
+        }
+        Some(("check-examples", _)) => crate::tasks::check_examples(),
+        Some(("test-unit", _)) => crate::tasks::test_unit(),
+        Some(("test-integration", _)) => crate::tasks::test_integration(),
+        Some(("slow-tests", sm)) => crate::tasks::slow_tests(
+            *sm.get_one::<u64>("threshold")
+                .context("please provide a threshold")?,
+        ),
+        Some(("docs", sm)) => DocsBuilder::default()
+            .watch(!sm.contains_id("once"))
+            .all_features(sm.contains_id("all-features"))
+            .private_items(sm.contains_id("private"))
+            .open(sm.contains_id("open"))
+            .deny_broken_links(sm.contains_id("deny-broken-links"))
+            .run(),
+        Some(("audit", sm)) => AuditBuilder::default()
+            .deny_warnings(sm.contains_id("deny-warnings"))
+            .ignore(
+                sm.get_many::<String>("ignore")
+                    .into_iter()
+                    .flatten()
+                    .cloned()
+                    .collect::<Vec<_>>(),
+            )
+            .run(),
+        Some(("deny", sm)) => DenyBuilder::default()
+            .checks(
+                sm.get_many::<String>("check")
+                    .into_iter()
+                    .flatten()
+                    .cloned()
+                    .collect::<Vec<_>>(),
+            )
+            .run(),
+        Some(("outdated", sm)) => OutdatedBuilder::default()
+            .workspace(sm.contains_id("workspace"))
+            .exit_on_outdated(sm.contains_id("exit-code"))
+            .run(),
+        Some(("release", sm)) => {
+            let mut builder = ReleaseBuilder::default();
+            let version = sm
+                .get_one::<String>("version")
+                .context("please provide a version")?;
+            match version.parse::<Bump>() {
+                Ok(bump) => builder.bump(bump),
+                Err(_) => builder.version(version.clone()),
+            };
+            builder
+                .packages(
+                    sm.get_many::<String>("package")
+                        .into_iter()
+                        .flatten()
+                        .cloned()
+                        .collect::<Vec<_>>(),
+                )
+                .commit(sm.contains_id("commit"))
+                .tag(sm.contains_id("tag"))
+                .dry_run(sm.contains_id("dry-run"))
+                .run()
+        }
+        Some(("changelog", sm)) => ChangelogBuilder::default()
+            .unreleased(sm.contains_id("unreleased"))
+            .tag(sm.get_one::<String>("tag").cloned())
+            .output(sm.get_one::<PathBuf>("output").cloned())
+            .run(),
+        Some(("doc-coverage", sm)) => DocCoverageBuilder::default()
+            .package(sm.get_one::<String>("package").cloned())
+            .min_coverage(sm.get_one::<f32>("min-coverage").copied())
+            .run(),
+        Some(("semver-checks", sm)) => SemverChecksBuilder::default()
+            .package(sm.get_one::<String>("package").cloned())
+            .baseline_version(sm.get_one::<String>("baseline-version").cloned())
+            .baseline_rev(sm.get_one::<String>("baseline-rev").cloned())
+            .run(),
+        Some(("msrv", sm)) => MsrvBuilder::default()
+            .version(sm.get_one::<String>("version").cloned())
+            .run(),
+        Some(("udeps", sm)) => UdepsBuilder::default()
+            .all_features(sm.contains_id("all-features"))
+            .run(),
+        Some(("bench", sm)) => BenchBuilder::default()
+            .package(sm.get_one::<String>("package").cloned())
+            .bench_name(sm.get_one::<String>("bench").cloned())
+            .baseline(sm.get_one::<String>("baseline").cloned())
+            .save_baseline(sm.get_one::<String>("save-baseline").cloned())
+            .run(),
+        Some(("flamegraph", sm)) => FlamegraphBuilder::default()
+            .bin(sm.get_one::<String>("bin").cloned())
+            .example(sm.get_one::<String>("example").cloned())
+            .bench(sm.get_one::<String>("bench").cloned())
+            .output(sm.get_one::<PathBuf>("output").cloned())
+            .args(
+                sm.get_many::<String>("args")
+                    .into_iter()
+                    .flatten()
+                    .cloned()
+                    .collect::<Vec<_>>(),
+            )
+            .run(),
+        Some(("install", sm)) => {
+            let mut builder = InstallBuilder::default();
+            if let Some(tools) = sm.get_many::<String>("tool") {
+                builder.tools(tools.map(|t| t.parse()).collect::<Result<Vec<Tool>, _>>()?);
+            }
+            builder
+                .force(sm.contains_id("force"))
+                .use_binstall(sm.contains_id("binstall"))
+                .retries(sm.get_one::<usize>("retries").copied().unwrap_or(0))
+                .run()
+        }
+        Some(("update-tools", sm)) => crate::tasks::update_tools(
+            &sm.get_many::<String>("tool")
+                .map(|t| t.map(|t| t.parse()).collect::<Result<Vec<Tool>, _>>())
+                .transpose()?
+                .unwrap_or_default(),
+        ),
+        Some(("powerset", sm)) => {
+            let mut builder = PowersetBuilder::default();
+            builder.feature_sets(
+                sm.get_many::<String>("features")
+                    .into_iter()
+                    .flatten()
+                    .map(|set| set.split(',').map(String::from).collect())
+                    .collect::<Vec<Vec<String>>>(),
+            );
+            for step in sm.get_many::<String>("skip").into_iter().flatten() {
+                match step.as_str() {
+                    "clippy" => builder.run_clippy(false),
+                    "tests" => builder.run_tests(false),
+                    _ => builder.run_doc_tests(false),
+                };
+            }
+            builder.run()
+        }
+        Some(("each-feature", _)) => crate::tasks::each_feature(),
+        Some(("check-no-default-features", _)) => crate::tasks::check_no_default_features(),
+        Some(("check-changed", sm)) => crate::tasks::check_changed(
+            sm.get_one::<String>("base")
+                .context("please provide a base revision")?,
+        ),
+        Some(("for-each-member", sm)) => crate::tasks::for_each_member(
+            sm.get_one::<String>("command")
+                .context("please provide a cargo command")?,
+            &sm.get_many::<String>("args")
+                .map(|a| a.map(String::as_str).collect::<Vec<_>>())
+                .unwrap_or_default(),
+        ),
+        Some(("minimal-versions", _)) => crate::tasks::minimal_versions(),
+        Some(("dep-consistency", sm)) => {
+            crate::tasks::check_dep_consistency(sm.contains_id("deny"))
+        }
+        Some(("deps-snapshot", sm)) => crate::tasks::deps_snapshot(
+            sm.get_one::<String>("file")
+                .context("please provide a snapshot file")?,
+        ),
+        Some(("deps-check", sm)) => crate::tasks::deps_check(
+            sm.get_one::<String>("file")
+                .context("please provide a snapshot file")?,
+        ),
+        Some(("doc-fences", sm)) => crate::tasks::check_doc_fences(sm.contains_id("deny")),
+        Some(("forbidden", sm)) => crate::tasks::check_forbidden(
+            &sm.get_many::<String>("pattern")
+                .map(|p| p.map(String::as_str).collect::<Vec<_>>())
+                .unwrap_or_default(),
+        ),
+        Some(("spellcheck", sm)) => SpellcheckBuilder::default()
+            .write(sm.contains_id("write"))
+            .run(),
+        Some(("fmt-toml", sm)) => crate::tasks::fmt_toml(sm.contains_id("check")),
+        Some(("fuzz", sm)) => crate::tasks::fuzz(
+            sm.get_one::<String>("target").map_or("", String::as_str),
+            *sm.get_one::<u64>("duration")
+                .context("please provide a duration")?,
+        ),
+        Some(("geiger", sm)) => GeigerBuilder::default()
+            .max_unsafe(sm.get_one::<u64>("max-unsafe").copied())
+            .run(),
+        Some(("licenses", sm)) => LicensesBuilder::default()
+            .format(
+                sm.get_one::<String>("format")
+                    .context("please provide a format")?
+                    .as_str(),
+            )
+            .output(sm.get_one::<String>("output").map(PathBuf::from))
+            .run(),
+        Some(("miri", sm)) => MiriBuilder::default()
+            .miriflags(sm.get_one::<String>("flags").cloned())
+            .install(sm.contains_id("install"))
+            .run(),
         Some(("bloat-deps", sm)) => crate::tasks::bloat_deps(
             sm.get_one::<String>("package")
                 .context("please provide a package with -p")?,
         ),
+        Some(("bloat-functions", sm)) => crate::tasks::bloat_functions(
+            sm.get_one::<String>("package")
+                .context("please provide a package with -p")?,
+            *sm.get_one::<usize>("count")
+                .context("please provide a count with -n")?,
+        ),
         Some(("bloat-time", sm)) => crate::tasks::bloat_time(
             sm.get_one::<String>("package")
                 .context("please provide a package with -p")?,
@@ -354,3 +4591,370 @@ pub fn main() -> AnyResult<()> {
     };
     res
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn docs_one_shot_with_all_features() {
+        let docs = DocsBuilder::default()
+            .watch(false)
+            .all_features(true)
+            .private_items(true)
+            .open(true)
+            .build()
+            .unwrap();
+        assert_eq!(
+            docs.doc_args(),
+            [
+                "doc",
+                "--no-deps",
+                "--all-features",
+                "--document-private-items",
+                "--open"
+            ]
+        );
+    }
+
+    #[test]
+    fn docs_watch_does_not_open() {
+        let docs = DocsBuilder::default().open(true).build().unwrap();
+        assert_eq!(docs.doc_args(), ["doc", "--no-deps"]);
+    }
+
+    #[test]
+    fn install_pins_versions() {
+        let install = InstallBuilder::default()
+            .versions(HashMap::from([(Tool::Grcov, "0.8.13".to_string())]))
+            .build()
+            .unwrap();
+        assert_eq!(
+            install.install_args(Tool::Grcov),
+            ["install", "grcov", "--version", "0.8.13", "--locked"]
+        );
+        assert_eq!(
+            install.install_args(Tool::Hack),
+            ["install", "cargo-hack", "--locked"]
+        );
+    }
+
+    #[test]
+    fn powerset_passes_feature_filters_to_every_run() {
+        let powerset = PowersetBuilder::default()
+            .include_features(vec!["serde".to_string(), "async".to_string()])
+            .exclude_features(vec!["std".to_string()])
+            .build()
+            .unwrap();
+        let commands = powerset.commands();
+        assert_eq!(commands.len(), 3);
+        for args in &commands {
+            assert!(
+                args.windows(2)
+                    .any(|w| w == ["--include-features", "serde,async"]),
+                "{args:?}"
+            );
+            assert!(
+                args.windows(2).any(|w| w == ["--exclude-features", "std"]),
+                "{args:?}"
+            );
+        }
+        assert_eq!(commands[0][..2], ["hack", "clippy"]);
+        assert_eq!(commands[1].last().unwrap(), "test");
+        assert_eq!(commands[2].last().unwrap(), "--doc");
+    }
+
+    #[test]
+    fn forbidden_tokens_in_comments_and_strings_are_ignored() {
+        let source = r##"
+fn main() {
+    // dbg!(x) in a comment
+    /* todo!() in a /* nested */ block */
+    let s = "dbg!(x) with \" an escaped quote";
+    let r = r#"todo!("raw")"#;
+    let c = '"';
+    let t: &'static str = "x";
+    dbg!(s);
+}
+"##;
+        let code = strip_comments_and_literals(source);
+        assert_eq!(code.lines().count(), source.lines().count());
+        let hits: Vec<_> = code
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| DEFAULT_FORBIDDEN.iter().any(|p| line.contains(p)))
+            .map(|(idx, _)| idx)
+            .collect();
+        assert_eq!(hits, [8]);
+        assert!(code.contains("&'static str"));
+    }
+
+    #[test]
+    fn sources_have_no_forbidden_tokens() {
+        check_forbidden(&[]).unwrap();
+    }
+
+    fn step_names(ci: &mut CIBuilder) -> Vec<String> {
+        let steps = ci.build().unwrap().steps().unwrap();
+        steps.into_iter().map(|step| step.name).collect()
+    }
+
+    #[test]
+    fn ci_plans_all_steps_by_default() {
+        assert_eq!(
+            step_names(&mut CIBuilder::default()),
+            ["fmt", "clippy", "tests", "doc tests"]
+        );
+    }
+
+    #[test]
+    fn ci_skips_disabled_steps() {
+        assert_eq!(
+            step_names(CIBuilder::default().run_doc_tests(false)),
+            ["fmt", "clippy", "tests"]
+        );
+        assert_eq!(
+            step_names(CIBuilder::default().run_fmt(false).run_tests(false)),
+            ["clippy", "doc tests"]
+        );
+        assert_eq!(
+            step_names(
+                CIBuilder::default()
+                    .run_clippy(false)
+                    .targets(vec!["wasm32-unknown-unknown".to_string()])
+            ),
+            ["fmt", "tests", "doc tests"]
+        );
+    }
+
+    #[test]
+    fn ci_deny_warnings_uses_rustdocflags_for_doc_tests() {
+        let steps = CIBuilder::default()
+            .check_examples(true)
+            .build()
+            .unwrap()
+            .steps()
+            .unwrap();
+        let vars: Vec<(&str, Option<&str>)> = steps
+            .iter()
+            .map(|step| (step.name.as_str(), step.warnings_var))
+            .collect();
+        assert_eq!(
+            vars,
+            [
+                ("fmt", None),
+                ("clippy", None),
+                ("tests", Some("RUSTFLAGS")),
+                ("doc tests", Some("RUSTDOCFLAGS")),
+                ("examples doc tests", None),
+                ("examples build", Some("RUSTFLAGS")),
+            ]
+        );
+    }
+
+    fn step_args(ci: &mut CIBuilder, name: &str) -> Vec<String> {
+        let steps = ci.build().unwrap().steps().unwrap();
+        steps
+            .into_iter()
+            .find(|step| step.name == name)
+            .map(|step| step.args)
+            .unwrap_or_else(|| panic!("no `{name}` step"))
+    }
+
+    #[test]
+    fn ci_passes_feature_flags_to_clippy_and_tests() {
+        let mut ci = CIBuilder::default();
+        ci.features(vec!["serde".to_string(), "log".to_string()])
+            .no_default_features(true);
+        for step in ["clippy", "tests"] {
+            let args = step_args(&mut ci, step);
+            assert!(
+                args.windows(2).any(|w| w == ["--features", "serde,log"]),
+                "{step}: {args:?}"
+            );
+            assert!(
+                args.contains(&"--no-default-features".to_string()),
+                "{step}: {args:?}"
+            );
+        }
+
+        let mut ci = CIBuilder::default();
+        ci.all_features(true).nextest(true);
+        for step in ["clippy", "tests"] {
+            let args = step_args(&mut ci, step);
+            assert!(
+                args.contains(&"--all-features".to_string()),
+                "{step}: {args:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn ci_excludes_xtask_by_default() {
+        for step in ["clippy", "tests", "doc tests"] {
+            let args = step_args(&mut CIBuilder::default(), step);
+            assert!(
+                args.windows(3)
+                    .any(|w| w == ["--workspace", "--exclude", "xtask"]),
+                "{step}: {args:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn ci_rejects_all_features_with_a_list() {
+        let ci = CIBuilder::default()
+            .all_features(true)
+            .features(vec!["serde".to_string()])
+            .build()
+            .unwrap();
+        assert!(ci.steps().is_err());
+    }
+
+    #[test]
+    fn clippy_denies_warnings_by_default() {
+        assert_eq!(clippy_lint_args(false, &[]), ["--", "-D", "warnings"]);
+    }
+
+    #[test]
+    fn clippy_max_expands_to_default_lints() {
+        let args = clippy_lint_args(true, &[]);
+        assert_eq!(args[..3], ["--", "-D", "warnings"]);
+        assert_eq!(args[3..], *CLIPPY_MAX_LINTS);
+    }
+
+    #[test]
+    fn clippy_custom_lints_replace_max() {
+        let custom = [
+            "-W",
+            "clippy::pedantic",
+            "-A",
+            "clippy::module_name_repetitions",
+        ]
+        .map(String::from);
+        assert_eq!(
+            clippy_lint_args(true, &custom),
+            [
+                "--",
+                "-D",
+                "warnings",
+                "-W",
+                "clippy::pedantic",
+                "-A",
+                "clippy::module_name_repetitions"
+            ]
+        );
+    }
+
+    #[test]
+    fn clippy_custom_warnings_level_overrides_deny() {
+        for level in [["-A", "warnings"], ["-W", "warnings"], ["-D", "warnings"]] {
+            let custom = level.map(String::from);
+            assert_eq!(
+                clippy_lint_args(false, &custom),
+                ["--", level[0], "warnings"]
+            );
+        }
+        let custom = ["--allow=warnings".to_string()];
+        assert_eq!(clippy_lint_args(false, &custom), ["--", "--allow=warnings"]);
+    }
+
+    #[test]
+    fn bump_version_drops_pre_release_and_build() {
+        assert_eq!(bump_version("1.2.3", Bump::Major).unwrap(), "2.0.0");
+        assert_eq!(bump_version("1.2.3", Bump::Minor).unwrap(), "1.3.0");
+        assert_eq!(bump_version("1.2.3", Bump::Patch).unwrap(), "1.2.4");
+        assert_eq!(
+            bump_version("0.9.0-rc.1+build.5", Bump::Patch).unwrap(),
+            "0.9.1"
+        );
+        assert!(bump_version("1.2", Bump::Patch).is_err());
+        assert!(bump_version("one.two.three", Bump::Patch).is_err());
+    }
+
+    #[test]
+    fn set_str_keeps_decor_and_comments() {
+        let mut doc: DocumentMut = "[package]\nversion   =   \"0.1.0\"   # keep me\n"
+            .parse()
+            .unwrap();
+        set_str(&mut doc["package"]["version"], "0.2.0");
+        assert_eq!(
+            doc.to_string(),
+            "[package]\nversion   =   \"0.2.0\"   # keep me\n"
+        );
+    }
+
+    #[test]
+    fn workspace_version_inheritance_is_detected() {
+        let inherited: DocumentMut = "[package]\nname = \"a\"\nversion.workspace = true\n"
+            .parse()
+            .unwrap();
+        let inline: DocumentMut = "[package]\nversion = { workspace = true }\n"
+            .parse()
+            .unwrap();
+        let own: DocumentMut = "[package]\nversion = \"0.1.0\"\n".parse().unwrap();
+        assert!(inherits_workspace_version(&inherited));
+        assert!(inherits_workspace_version(&inline));
+        assert!(!inherits_workspace_version(&own));
+    }
+
+    #[test]
+    fn dependency_versions_follow_released_packages() {
+        let manifest = r#"[dependencies]
+a = { version = "0.1.0", path = "../a" } # local
+renamed = { package = "b", version = "0.1.0", path = "../b" }
+inherited = { workspace = true }
+serde = "1"
+
+[dev-dependencies]
+a = { path = "../a", version = "0.1.0" }
+
+[target.'cfg(unix)'.dependencies]
+b = { version = "0.1.0", path = "../b" }
+
+[target.'cfg(windows)'.build-dependencies.a]
+version = "0.1.0"
+path = "../a"
+"#;
+        let mut doc: DocumentMut = manifest.parse().unwrap();
+        let released = BTreeMap::from([
+            ("a".to_string(), "0.2.0".to_string()),
+            ("b".to_string(), "1.0.0".to_string()),
+        ]);
+        set_dependency_versions(doc.as_table_mut(), &released);
+        assert_eq!(
+            doc.to_string(),
+            r#"[dependencies]
+a = { version = "0.2.0", path = "../a" } # local
+renamed = { package = "b", version = "1.0.0", path = "../b" }
+inherited = { workspace = true }
+serde = "1"
+
+[dev-dependencies]
+a = { path = "../a", version = "0.2.0" }
+
+[target.'cfg(unix)'.dependencies]
+b = { version = "1.0.0", path = "../b" }
+
+[target.'cfg(windows)'.build-dependencies.a]
+version = "0.2.0"
+path = "../a"
+"#
+        );
+    }
+
+    #[test]
+    fn workspace_dependency_versions_follow_released_packages() {
+        let mut doc: DocumentMut = r#"[workspace.dependencies]
+a = { version = "0.1.0", path = "a" }
+"#
+        .parse()
+        .unwrap();
+        let released = BTreeMap::from([("a".to_string(), "0.2.0".to_string())]);
+        set_dependency_versions(doc["workspace"].as_table_mut().unwrap(), &released);
+        assert_eq!(
+            doc.to_string(),
+            "[workspace.dependencies]\na = { version = \"0.2.0\", path = \"a\" }\n"
+        );
+    }
+}
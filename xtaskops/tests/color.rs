@@ -0,0 +1,19 @@
+use std::env;
+
+use xtaskops::ops::{set_color, ColorChoice};
+
+// the color choice and `CARGO_TERM_COLOR` are process wide, so everything is
+// checked in a single test
+#[test]
+fn auto_color_clears_cargo_term_color() {
+    env::remove_var("NO_COLOR");
+
+    set_color(ColorChoice::Always);
+    assert_eq!(env::var("CARGO_TERM_COLOR").unwrap(), "always");
+    set_color(ColorChoice::Auto);
+    assert!(env::var_os("CARGO_TERM_COLOR").is_none());
+
+    env::set_var("NO_COLOR", "1");
+    set_color(ColorChoice::Auto);
+    assert_eq!(env::var("CARGO_TERM_COLOR").unwrap(), "never");
+}
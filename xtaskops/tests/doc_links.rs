@@ -0,0 +1,22 @@
+use std::env;
+use std::path::Path;
+
+use xtaskops::ops::copy_contents;
+use xtaskops::tasks::DocsBuilder;
+
+#[test]
+fn deny_broken_links_fails_on_a_broken_link() {
+    let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/broken_doc_link");
+    let tmp = tempfile::tempdir().unwrap();
+    copy_contents(&fixture, tmp.path(), false).unwrap();
+    env::set_current_dir(tmp.path()).unwrap();
+    env::set_var("CARGO_TARGET_DIR", tmp.path().join("target"));
+    env::remove_var("RUSTDOCFLAGS");
+
+    let mut docs = DocsBuilder::default();
+    docs.watch(false).open(false);
+
+    // a broken link is only a warning unless it is denied
+    docs.run().unwrap();
+    assert!(docs.deny_broken_links(true).run().is_err());
+}
@@ -0,0 +1,31 @@
+use std::fs;
+
+use xtaskops::ops::{planned_operations, remove_dir, set_dry_run, write_atomic, PlannedOp};
+
+// dry-run mode is process wide, so everything is checked in a single test
+#[test]
+fn dry_run_plans_instead_of_changing_files() {
+    let tmp = tempfile::tempdir().unwrap();
+    let file = tmp.path().join("report.txt");
+    let dir = tmp.path().join("out");
+    fs::create_dir(&dir).unwrap();
+
+    set_dry_run(true);
+    write_atomic(&file, b"report").unwrap();
+    remove_dir(&dir).unwrap();
+    set_dry_run(false);
+
+    assert!(!file.exists());
+    assert!(dir.exists());
+    assert_eq!(
+        planned_operations(),
+        [
+            PlannedOp::WriteFile(file.clone()),
+            PlannedOp::RemoveDir(dir)
+        ]
+    );
+
+    write_atomic(&file, b"report").unwrap();
+    assert_eq!(fs::read_to_string(&file).unwrap(), "report");
+    assert!(planned_operations().is_empty());
+}
@@ -0,0 +1,6 @@
+//! A crate whose docs link to an item that does not exist
+
+/// Adds one, see [`does_not_exist`]
+pub fn add_one(x: u32) -> u32 {
+    x + 1
+}
@@ -0,0 +1,64 @@
+use std::fs;
+use std::path::Path;
+
+use xtaskops::ops::move_contents;
+
+fn populate(dir: &Path) {
+    fs::create_dir_all(dir.join("nested")).unwrap();
+    fs::write(dir.join("a.txt"), "new a").unwrap();
+    fs::write(dir.join("nested/b.txt"), "new b").unwrap();
+}
+
+#[test]
+fn moves_into_missing_destination() {
+    let tmp = tempfile::tempdir().unwrap();
+    let (from, to) = (tmp.path().join("from"), tmp.path().join("to"));
+    populate(&from);
+
+    let moved = move_contents(&from, &to, false).unwrap();
+
+    assert_eq!(moved, 10);
+    assert!(!from.exists());
+    assert_eq!(fs::read_to_string(to.join("a.txt")).unwrap(), "new a");
+    assert_eq!(
+        fs::read_to_string(to.join("nested/b.txt")).unwrap(),
+        "new b"
+    );
+}
+
+#[test]
+fn overwrites_existing_files_when_asked() {
+    let tmp = tempfile::tempdir().unwrap();
+    let (from, to) = (tmp.path().join("from"), tmp.path().join("to"));
+    populate(&from);
+    fs::create_dir_all(&to).unwrap();
+    fs::write(to.join("a.txt"), "old a").unwrap();
+    fs::write(to.join("kept.txt"), "kept").unwrap();
+
+    move_contents(&from, &to, true).unwrap();
+
+    assert!(!from.exists());
+    assert_eq!(fs::read_to_string(to.join("a.txt")).unwrap(), "new a");
+    assert_eq!(fs::read_to_string(to.join("kept.txt")).unwrap(), "kept");
+}
+
+#[test]
+fn refuses_to_overwrite_without_flag() {
+    let tmp = tempfile::tempdir().unwrap();
+    let (from, to) = (tmp.path().join("from"), tmp.path().join("to"));
+    populate(&from);
+    fs::create_dir_all(&to).unwrap();
+    fs::write(to.join("a.txt"), "old a").unwrap();
+
+    assert!(move_contents(&from, &to, false).is_err());
+    assert_eq!(fs::read_to_string(to.join("a.txt")).unwrap(), "old a");
+    assert!(from.join("a.txt").exists());
+}
+
+#[test]
+fn fails_on_missing_source() {
+    let tmp = tempfile::tempdir().unwrap();
+    let from = tmp.path().join("missing");
+
+    assert!(move_contents(&from, tmp.path().join("to"), false).is_err());
+}